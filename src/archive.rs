@@ -0,0 +1,162 @@
+use async_trait::async_trait;
+use futures::future;
+use solana_client::client_error::{ClientError, ClientErrorKind, Result as ClientResult};
+use solana_client::nonblocking::rpc_client::RpcClient;
+use solana_client::rpc_client::GetConfirmedSignaturesForAddress2Config;
+use solana_client::rpc_config::{RpcBlockConfig, RpcTransactionConfig};
+use solana_client::rpc_custom_error::{
+    JSON_RPC_SERVER_ERROR_BLOCK_CLEANED_UP, JSON_RPC_SERVER_ERROR_BLOCK_NOT_AVAILABLE,
+    JSON_RPC_SERVER_ERROR_LONG_TERM_STORAGE_SLOT_SKIPPED, JSON_RPC_SERVER_ERROR_LONG_TERM_STORAGE_UNREACHABLE,
+    JSON_RPC_SERVER_ERROR_SLOT_SKIPPED, JSON_RPC_SERVER_ERROR_TRANSACTION_HISTORY_NOT_AVAILABLE,
+};
+use solana_client::rpc_request::RpcError;
+use solana_client::rpc_response::{RpcConfirmedTransactionStatusWithSignature, RpcResult};
+use solana_sdk::{
+    account::Account, clock::Slot, clock::UnixTimestamp, commitment_config::CommitmentConfig, pubkey::Pubkey,
+    signature::Signature,
+};
+use solana_transaction_status::{EncodedConfirmedTransactionWithStatusMeta, TransactionStatus, UiConfirmedBlock};
+use tracing::debug;
+
+use crate::source::SolanaSource;
+
+/// Fetches transaction bodies from somewhere other than the primary RPC
+/// node, such as an archival provider with its own API. Implement this to
+/// plug one into [`ArchiveFallback`]; an [`RpcClient`] pointed at a node
+/// with full history works as is.
+#[async_trait]
+pub trait TransactionFetcher {
+    async fn get_transaction(
+        &self,
+        signature: &Signature,
+        config: RpcTransactionConfig,
+    ) -> ClientResult<EncodedConfirmedTransactionWithStatusMeta>;
+}
+
+#[async_trait]
+impl TransactionFetcher for RpcClient {
+    async fn get_transaction(
+        &self,
+        signature: &Signature,
+        config: RpcTransactionConfig,
+    ) -> ClientResult<EncodedConfirmedTransactionWithStatusMeta> {
+        RpcClient::get_transaction_with_config(self, signature, config).await
+    }
+}
+
+/// A [`SolanaSource`] that serves everything from `primary`, but asks
+/// `archive` for any transaction the primary reports as missing or pruned.
+/// Signatures still come from the primary, so the archive only needs to
+/// look transactions up. Other errors from the primary are returned as is.
+pub struct ArchiveFallback<S, F> {
+    primary: S,
+    archive: F,
+}
+
+impl<S, F> ArchiveFallback<S, F> {
+    pub fn new(primary: S, archive: F) -> Self {
+        ArchiveFallback { primary, archive }
+    }
+}
+
+#[async_trait]
+impl<S, F> SolanaSource for ArchiveFallback<S, F>
+where
+    S: SolanaSource + Sync,
+    F: TransactionFetcher + Sync,
+{
+    async fn get_signatures_for_address_with_config(
+        &self,
+        address: &Pubkey,
+        config: GetConfirmedSignaturesForAddress2Config,
+    ) -> ClientResult<Vec<RpcConfirmedTransactionStatusWithSignature>> {
+        self.primary.get_signatures_for_address_with_config(address, config).await
+    }
+
+    async fn get_transaction_with_config(
+        &self,
+        signature: &Signature,
+        config: RpcTransactionConfig,
+    ) -> ClientResult<EncodedConfirmedTransactionWithStatusMeta> {
+        match self.primary.get_transaction_with_config(signature, config).await {
+            Err(e) if is_unavailable(&e) => {
+                debug!("Transaction {} unavailable on the primary, trying the archive: {}", signature, e);
+                self.archive.get_transaction(signature, config).await
+            }
+            result => result,
+        }
+    }
+
+    /// Batches through the primary, then looks up whatever it was missing
+    /// in the archive one by one.
+    #[allow(clippy::result_large_err)]
+    async fn get_transactions_with_config(
+        &self,
+        signatures: &[Signature],
+        config: RpcTransactionConfig,
+    ) -> Vec<ClientResult<EncodedConfirmedTransactionWithStatusMeta>> {
+        let results = self.primary.get_transactions_with_config(signatures, config).await;
+        future::join_all(signatures.iter().zip(results).map(|(signature, result)| async move {
+            match result {
+                Err(e) if is_unavailable(&e) => {
+                    debug!("Transaction {} unavailable on the primary, trying the archive: {}", signature, e);
+                    self.archive.get_transaction(signature, config).await
+                }
+                result => result,
+            }
+        }))
+        .await
+    }
+
+    async fn get_signature_statuses_with_history(
+        &self,
+        signatures: &[Signature],
+    ) -> RpcResult<Vec<Option<TransactionStatus>>> {
+        self.primary.get_signature_statuses_with_history(signatures).await
+    }
+
+    async fn get_account_with_commitment(
+        &self,
+        pubkey: &Pubkey,
+        commitment: CommitmentConfig,
+    ) -> RpcResult<Option<Account>> {
+        self.primary.get_account_with_commitment(pubkey, commitment).await
+    }
+
+    async fn get_slot_with_commitment(&self, commitment: CommitmentConfig) -> ClientResult<Slot> {
+        self.primary.get_slot_with_commitment(commitment).await
+    }
+
+    async fn get_block_time(&self, slot: Slot) -> ClientResult<UnixTimestamp> {
+        self.primary.get_block_time(slot).await
+    }
+
+    async fn get_block_with_config(&self, slot: Slot, config: RpcBlockConfig) -> ClientResult<UiConfirmedBlock> {
+        self.primary.get_block_with_config(slot, config).await
+    }
+
+    fn cache_hits(&self) -> usize {
+        self.primary.cache_hits()
+    }
+}
+
+/// Whether `e` means the node doesn't have the transaction, as opposed to
+/// the request failing. `getTransaction` answers an unknown signature with a
+/// null result, which `RpcClient` fails to deserialize and a batch reports
+/// as not found; pruned history comes back as one of the storage errors.
+pub(crate) fn is_unavailable(e: &ClientError) -> bool {
+    match e.kind() {
+        ClientErrorKind::SerdeJson(e) => e.to_string().contains("null"),
+        ClientErrorKind::Custom(message) => message.contains("not found"),
+        ClientErrorKind::RpcError(RpcError::RpcResponseError { code, .. }) => [
+            JSON_RPC_SERVER_ERROR_BLOCK_CLEANED_UP,
+            JSON_RPC_SERVER_ERROR_BLOCK_NOT_AVAILABLE,
+            JSON_RPC_SERVER_ERROR_SLOT_SKIPPED,
+            JSON_RPC_SERVER_ERROR_LONG_TERM_STORAGE_SLOT_SKIPPED,
+            JSON_RPC_SERVER_ERROR_TRANSACTION_HISTORY_NOT_AVAILABLE,
+            JSON_RPC_SERVER_ERROR_LONG_TERM_STORAGE_UNREACHABLE,
+        ]
+        .contains(code),
+        _ => false,
+    }
+}
@@ -0,0 +1,160 @@
+//! Command-line front end: indexes one wallet and prints its transfers to
+//! stdout. Run with `--help` for the options; logging follows `RUST_LOG`.
+
+use chrono::Utc;
+use log::{error, warn};
+use solana_sdk::pubkey::Pubkey;
+use std::env;
+use std::io::{self, Write};
+use std::process::ExitCode;
+use std::str::FromStr;
+
+use solana_usdc_indexer::client::RpcClientConfig;
+use solana_usdc_indexer::config::IndexerConfig;
+use solana_usdc_indexer::error::IndexerError;
+use solana_usdc_indexer::export::{export_csv, export_jsonl};
+use solana_usdc_indexer::models::Transfer;
+use solana_usdc_indexer::time::{parse_time_range, TimeRange};
+
+const USAGE: &str = "\
+Usage: indexer-cli --wallet <ADDRESS> [OPTIONS]
+
+Options:
+  --rpc-url <URL>      RPC endpoint [default: $SOLANA_RPC_URL, else mainnet-beta]
+  --wallet <ADDRESS>   Wallet to index
+  --mint <ADDRESS>     Mint to track; repeat for several [default: USDC]
+  --start <TIME>       Range start: RFC 3339, `YYYY-MM-DD HH:MM[:SS]` or a date, in UTC
+  --end <TIME>         Range end, in the same forms; a bare date covers the whole day
+  --format <FORMAT>    csv, json or jsonl [default: csv]
+  -h, --help           Print this help
+
+Without --start and --end the last 4 days are indexed.";
+
+#[derive(Clone, Copy)]
+enum Format {
+    Csv,
+    Json,
+    Jsonl,
+}
+
+struct Args {
+    rpc_url: String,
+    wallet: String,
+    mints: Vec<Pubkey>,
+    range: TimeRange,
+    format: Format,
+}
+
+fn parse_args(mut args: impl Iterator<Item = String>) -> Result<Option<Args>, String> {
+    let mut rpc_url = None;
+    let mut wallet = None;
+    let mut mints = Vec::new();
+    let mut start = None;
+    let mut end = None;
+    let mut format = Format::Csv;
+
+    while let Some(arg) = args.next() {
+        // Both `--flag value` and `--flag=value` are accepted
+        let (flag, inline) = match arg.split_once('=') {
+            Some((flag, value)) if flag.starts_with("--") => (flag.to_string(), Some(value.to_string())),
+            _ => (arg, None),
+        };
+        if flag == "-h" || flag == "--help" {
+            return Ok(None);
+        }
+        let mut value = || inline.clone().or_else(|| args.next()).ok_or(format!("{} needs a value", flag));
+        match flag.as_str() {
+            "--rpc-url" => rpc_url = Some(value()?),
+            "--wallet" => wallet = Some(value()?),
+            "--mint" => {
+                let mint = value()?;
+                mints.push(Pubkey::from_str(&mint).map_err(|_| format!("Invalid mint: {}", mint))?);
+            }
+            "--start" => start = Some(value()?),
+            "--end" => end = Some(value()?),
+            "--format" => {
+                format = match value()?.as_str() {
+                    "csv" => Format::Csv,
+                    "json" => Format::Json,
+                    "jsonl" => Format::Jsonl,
+                    other => return Err(format!("Unknown format {}; expected csv, json or jsonl", other)),
+                }
+            }
+            other => return Err(format!("Unknown argument: {}", other)),
+        }
+    }
+
+    let range = match (start, end) {
+        (Some(start), Some(end)) => parse_time_range(&start, &end, &Utc).map_err(|e| e.to_string())?,
+        (None, None) => TimeRange::last_days(4),
+        _ => return Err("--start and --end must be given together".to_string()),
+    };
+    Ok(Some(Args {
+        rpc_url: rpc_url.unwrap_or_else(|| {
+            env::var("SOLANA_RPC_URL").unwrap_or("https://api.mainnet-beta.solana.com".to_string())
+        }),
+        wallet: wallet.ok_or("--wallet is required")?,
+        mints,
+        range,
+        format,
+    }))
+}
+
+fn write_transfers(transfers: &[Transfer], format: Format) -> Result<(), IndexerError> {
+    let stdout = io::stdout().lock();
+    match format {
+        Format::Csv => export_csv(transfers, stdout),
+        Format::Jsonl => export_jsonl(transfers, stdout),
+        Format::Json => {
+            let mut stdout = stdout;
+            serde_json::to_writer_pretty(&mut stdout, transfers)?;
+            writeln!(stdout)?;
+            Ok(())
+        }
+    }
+}
+
+#[tokio::main]
+async fn main() -> ExitCode {
+    env_logger::init();
+
+    let args = match parse_args(env::args().skip(1)) {
+        Ok(Some(args)) => args,
+        Ok(None) => {
+            println!("{}", USAGE);
+            return ExitCode::SUCCESS;
+        }
+        Err(e) => {
+            eprintln!("{}\n\n{}", e, USAGE);
+            return ExitCode::from(2);
+        }
+    };
+
+    let client = match RpcClientConfig::default().connect(args.rpc_url) {
+        Ok(client) => client,
+        Err(e) => {
+            error!("Failed to create RPC client: {}", e);
+            return ExitCode::FAILURE;
+        }
+    };
+    let mut config = IndexerConfig::new(args.wallet).time_range(args.range);
+    if !args.mints.is_empty() {
+        config = config.mints(args.mints);
+    }
+
+    let result = match config.run(&client).await {
+        Ok(result) => result,
+        Err(e) => {
+            error!("Failed to index transfers: {}", e);
+            return ExitCode::FAILURE;
+        }
+    };
+    for (signature, e) in &result.errors {
+        warn!("Could not fetch transaction {}: {}", signature, e);
+    }
+    if let Err(e) = write_transfers(&result.transfers, args.format) {
+        error!("Failed to write transfers: {}", e);
+        return ExitCode::FAILURE;
+    }
+    ExitCode::SUCCESS
+}
@@ -0,0 +1,51 @@
+use solana_sdk::{commitment_config::CommitmentConfig, pubkey::Pubkey};
+
+use crate::error::IndexerError;
+use crate::indexer::{index_token_transfers, IndexResult, ProgressEvent, TransferFilter};
+use crate::retry::RetryPolicy;
+use crate::source::SolanaSource;
+use crate::time::TimeRange;
+
+/// Runs [`index_token_transfers`] to completion on a fresh current-thread
+/// Tokio runtime, for synchronous call sites such as a plain `main`. Takes
+/// the same parameters. `client` must be an async source (for example the
+/// nonblocking `RpcClient`); it is driven by the runtime built here.
+///
+/// # Panics
+///
+/// Panics if called from within an async context, where blocking the thread
+/// would stall the surrounding runtime. Await [`index_token_transfers`]
+/// there instead.
+#[allow(clippy::too_many_arguments)]
+pub fn index_token_transfers_blocking(
+    client: &(impl SolanaSource + Sync),
+    wallet: &str,
+    mints: &[Pubkey],
+    range: TimeRange,
+    page_limit: usize,
+    concurrency: usize,
+    batch_size: usize,
+    retry: RetryPolicy,
+    filter: &TransferFilter,
+    commitment: CommitmentConfig,
+    progress: Option<&(dyn Fn(ProgressEvent) + Sync)>,
+) -> Result<IndexResult, IndexerError> {
+    assert!(
+        tokio::runtime::Handle::try_current().is_err(),
+        "index_token_transfers_blocking called from within an async runtime"
+    );
+    let runtime = tokio::runtime::Builder::new_current_thread().enable_all().build()?;
+    runtime.block_on(index_token_transfers(
+        client,
+        wallet,
+        mints,
+        range,
+        page_limit,
+        concurrency,
+        batch_size,
+        retry,
+        filter,
+        commitment,
+        progress,
+    ))
+}
@@ -0,0 +1,43 @@
+use solana_client::rpc_config::RpcBlockConfig;
+use solana_sdk::{clock::Slot, commitment_config::CommitmentConfig};
+use solana_transaction_status::TransactionDetails;
+use std::collections::{BTreeSet, HashMap};
+use tracing::warn;
+
+use crate::models::Transfer;
+use crate::retry::{retry_with_backoff, RetryPolicy};
+use crate::source::SolanaSource;
+
+/// Sets `block_hash` on each transfer to the hash of the block at its slot.
+/// Each distinct slot costs one `getBlock` call (without transactions or
+/// rewards), however many transfers share it. Slots whose block can't be
+/// fetched, e.g. on nodes that have pruned it, are left as `None`.
+pub async fn enrich_with_block_hashes(
+    client: &(impl SolanaSource + Sync),
+    transfers: &mut [Transfer],
+    retry: &RetryPolicy,
+    commitment: CommitmentConfig,
+) {
+    let config = RpcBlockConfig {
+        transaction_details: Some(TransactionDetails::None),
+        rewards: Some(false),
+        commitment: Some(commitment),
+        max_supported_transaction_version: Some(0),
+        ..RpcBlockConfig::default()
+    };
+
+    let slots: BTreeSet<Slot> = transfers.iter().map(|t| t.slot).collect();
+    let mut hashes = HashMap::new();
+    for slot in slots {
+        match retry_with_backoff(retry, || client.get_block_with_config(slot, config)).await {
+            Ok(block) => {
+                hashes.insert(slot, block.blockhash);
+            }
+            Err(e) => warn!("No block available for slot {}: {}", slot, e),
+        }
+    }
+
+    for t in transfers.iter_mut() {
+        t.block_hash = hashes.get(&t.slot).cloned();
+    }
+}
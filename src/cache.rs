@@ -0,0 +1,251 @@
+use async_trait::async_trait;
+use solana_client::client_error::{ClientErrorKind, Result as ClientResult};
+use solana_client::rpc_client::GetConfirmedSignaturesForAddress2Config;
+use solana_client::rpc_config::{RpcBlockConfig, RpcTransactionConfig};
+use solana_client::rpc_response::{RpcConfirmedTransactionStatusWithSignature, RpcResult};
+use solana_sdk::{
+    account::Account,
+    clock::Slot,
+    clock::UnixTimestamp,
+    commitment_config::{CommitmentConfig, CommitmentLevel},
+    pubkey::Pubkey,
+    signature::Signature,
+};
+use solana_transaction_status::{
+    EncodedConfirmedTransactionWithStatusMeta, TransactionStatus, UiConfirmedBlock, UiTransactionEncoding,
+};
+use std::fs::{self, File};
+use std::io::{BufReader, BufWriter, ErrorKind, Write};
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use tracing::warn;
+
+use crate::error::IndexerError;
+use crate::source::SolanaSource;
+
+/// Store of fetched transactions keyed by signature and the commitment they
+/// were fetched at. Entries are never invalidated. A `finalized` transaction
+/// never changes, but one fetched at a lower commitment can still be rolled
+/// back, so it is kept apart and only answers lookups at that commitment.
+pub trait TransactionCache {
+    fn get(
+        &self,
+        signature: &Signature,
+        commitment: CommitmentLevel,
+    ) -> Result<Option<EncodedConfirmedTransactionWithStatusMeta>, IndexerError>;
+    fn put(
+        &self,
+        signature: &Signature,
+        commitment: CommitmentLevel,
+        tx: &EncodedConfirmedTransactionWithStatusMeta,
+    ) -> Result<(), IndexerError>;
+}
+
+/// Caches each transaction as `<signature>.<commitment>.json` inside a
+/// directory, holding the encoded transaction exactly as the RPC returned it.
+#[derive(Clone, Debug)]
+pub struct FileTransactionCache {
+    dir: PathBuf,
+}
+
+impl FileTransactionCache {
+    /// Uses `dir` as the cache directory, creating it if needed.
+    pub fn new(dir: impl Into<PathBuf>) -> Result<Self, IndexerError> {
+        let dir = dir.into();
+        fs::create_dir_all(&dir)?;
+        Ok(FileTransactionCache { dir })
+    }
+
+    fn path(&self, signature: &Signature, commitment: CommitmentLevel) -> PathBuf {
+        self.dir.join(format!("{}.{}.json", signature, commitment))
+    }
+}
+
+impl TransactionCache for FileTransactionCache {
+    fn get(
+        &self,
+        signature: &Signature,
+        commitment: CommitmentLevel,
+    ) -> Result<Option<EncodedConfirmedTransactionWithStatusMeta>, IndexerError> {
+        let file = match File::open(self.path(signature, commitment)) {
+            Ok(file) => file,
+            Err(e) if e.kind() == ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(e.into()),
+        };
+        Ok(Some(serde_json::from_reader(BufReader::new(file))?))
+    }
+
+    fn put(
+        &self,
+        signature: &Signature,
+        commitment: CommitmentLevel,
+        tx: &EncodedConfirmedTransactionWithStatusMeta,
+    ) -> Result<(), IndexerError> {
+        // Write to a temporary file first so an interrupted run never leaves a truncated entry
+        let path = self.path(signature, commitment);
+        let tmp = path.with_extension("json.tmp");
+        let mut writer = BufWriter::new(File::create(&tmp)?);
+        serde_json::to_writer(&mut writer, tx)?;
+        writer.flush()?;
+        fs::rename(&tmp, &path)?;
+        Ok(())
+    }
+}
+
+/// A [`SolanaSource`] that answers transaction lookups from `cache` when it
+/// can and stores whatever it has to fetch from `source`. Cache failures are
+/// logged and fall through to the underlying source. Only `jsonParsed`
+/// lookups with an explicit commitment, which the indexer makes, are cached;
+/// anything else passes straight through.
+pub struct CachedSource<S, C> {
+    source: S,
+    cache: C,
+    hits: AtomicUsize,
+}
+
+impl<S, C> CachedSource<S, C> {
+    pub fn new(source: S, cache: C) -> Self {
+        CachedSource {
+            source,
+            cache,
+            hits: AtomicUsize::new(0),
+        }
+    }
+}
+
+#[async_trait]
+impl<S, C> SolanaSource for CachedSource<S, C>
+where
+    S: SolanaSource + Sync,
+    C: TransactionCache + Sync,
+{
+    async fn get_signatures_for_address_with_config(
+        &self,
+        address: &Pubkey,
+        config: GetConfirmedSignaturesForAddress2Config,
+    ) -> ClientResult<Vec<RpcConfirmedTransactionStatusWithSignature>> {
+        self.source.get_signatures_for_address_with_config(address, config).await
+    }
+
+    async fn get_transaction_with_config(
+        &self,
+        signature: &Signature,
+        config: RpcTransactionConfig,
+    ) -> ClientResult<EncodedConfirmedTransactionWithStatusMeta> {
+        let Some(commitment) = cache_commitment(&config) else {
+            return self.source.get_transaction_with_config(signature, config).await;
+        };
+        match self.cache.get(signature, commitment) {
+            Ok(Some(tx)) => {
+                self.hits.fetch_add(1, Ordering::Relaxed);
+                return Ok(tx);
+            }
+            Ok(None) => {}
+            Err(e) => warn!("Failed to read cached transaction {}: {}", signature, e),
+        }
+
+        let tx = self.source.get_transaction_with_config(signature, config).await?;
+        if let Err(e) = self.cache.put(signature, commitment, &tx) {
+            warn!("Failed to cache transaction {}: {}", signature, e);
+        }
+        Ok(tx)
+    }
+
+    #[allow(clippy::result_large_err)]
+    async fn get_transactions_with_config(
+        &self,
+        signatures: &[Signature],
+        config: RpcTransactionConfig,
+    ) -> Vec<ClientResult<EncodedConfirmedTransactionWithStatusMeta>> {
+        let Some(commitment) = cache_commitment(&config) else {
+            return self.source.get_transactions_with_config(signatures, config).await;
+        };
+        let mut results: Vec<_> = signatures
+            .iter()
+            .map(|signature| match self.cache.get(signature, commitment) {
+                Ok(cached) => cached.map(Ok),
+                Err(e) => {
+                    warn!("Failed to read cached transaction {}: {}", signature, e);
+                    None
+                }
+            })
+            .collect();
+
+        let hits = results.iter().filter(|cached| cached.is_some()).count();
+        self.hits.fetch_add(hits, Ordering::Relaxed);
+
+        let misses: Vec<Signature> = signatures
+            .iter()
+            .zip(&results)
+            .filter(|(_, cached)| cached.is_none())
+            .map(|(signature, _)| *signature)
+            .collect();
+        if !misses.is_empty() {
+            let mut fetched = self.source.get_transactions_with_config(&misses, config).await.into_iter();
+            for (signature, slot) in signatures.iter().zip(results.iter_mut()) {
+                if slot.is_some() {
+                    continue;
+                }
+                let Some(tx) = fetched.next() else {
+                    break;
+                };
+                if let Ok(tx) = &tx {
+                    if let Err(e) = self.cache.put(signature, commitment, tx) {
+                        warn!("Failed to cache transaction {}: {}", signature, e);
+                    }
+                }
+                *slot = Some(tx);
+            }
+        }
+
+        results
+            .into_iter()
+            .zip(signatures)
+            .map(|(result, signature)| {
+                result.unwrap_or_else(|| {
+                    Err(ClientErrorKind::Custom(format!("No result for transaction {}", signature)).into())
+                })
+            })
+            .collect()
+    }
+
+    async fn get_signature_statuses_with_history(
+        &self,
+        signatures: &[Signature],
+    ) -> RpcResult<Vec<Option<TransactionStatus>>> {
+        self.source.get_signature_statuses_with_history(signatures).await
+    }
+
+    async fn get_account_with_commitment(
+        &self,
+        pubkey: &Pubkey,
+        commitment: CommitmentConfig,
+    ) -> RpcResult<Option<Account>> {
+        self.source.get_account_with_commitment(pubkey, commitment).await
+    }
+
+    async fn get_slot_with_commitment(&self, commitment: CommitmentConfig) -> ClientResult<Slot> {
+        self.source.get_slot_with_commitment(commitment).await
+    }
+
+    async fn get_block_time(&self, slot: Slot) -> ClientResult<UnixTimestamp> {
+        self.source.get_block_time(slot).await
+    }
+
+    async fn get_block_with_config(&self, slot: Slot, config: RpcBlockConfig) -> ClientResult<UiConfirmedBlock> {
+        self.source.get_block_with_config(slot, config).await
+    }
+
+    fn cache_hits(&self) -> usize {
+        self.hits.load(Ordering::Relaxed) + self.source.cache_hits()
+    }
+}
+
+/// The commitment to key a lookup's cache entry on, or `None` if the lookup
+/// shouldn't be cached.
+fn cache_commitment(config: &RpcTransactionConfig) -> Option<CommitmentLevel> {
+    if config.encoding != Some(UiTransactionEncoding::JsonParsed) {
+        return None;
+    }
+    config.commitment.map(|commitment| commitment.commitment)
+}
@@ -0,0 +1,146 @@
+use async_trait::async_trait;
+use reqwest::header::{HeaderName, HeaderValue};
+use solana_client::client_error::{ClientError, Result as ClientResult};
+use solana_client::nonblocking::rpc_client::RpcClient;
+use solana_client::rpc_client::GetConfirmedSignaturesForAddress2Config;
+use solana_client::rpc_config::{RpcBlockConfig, RpcTransactionConfig};
+use solana_client::rpc_response::{RpcConfirmedTransactionStatusWithSignature, RpcResult};
+use solana_rpc_client::http_sender::HttpSender;
+use solana_rpc_client::rpc_client::RpcClientConfig as SenderConfig;
+use solana_sdk::{
+    account::Account, clock::Slot, clock::UnixTimestamp, commitment_config::CommitmentConfig, pubkey::Pubkey,
+    signature::Signature,
+};
+use solana_transaction_status::{EncodedConfirmedTransactionWithStatusMeta, TransactionStatus, UiConfirmedBlock};
+use std::time::Duration;
+
+use crate::error::IndexerError;
+use crate::source::{batch_failure, send_transaction_batch, SolanaSource};
+
+/// HTTP settings for an RPC connection that `RpcClient::new` doesn't
+/// expose. Without a timeout a stalled endpoint hangs the run, so one is
+/// always set.
+#[derive(Clone, Debug)]
+pub struct RpcClientConfig {
+    /// Limit on each HTTP request, from connecting to reading the body.
+    pub timeout: Duration,
+    /// Default commitment for calls that don't pass their own.
+    pub commitment: CommitmentConfig,
+    /// Extra headers sent with every request, such as a provider API key.
+    pub headers: Vec<(String, String)>,
+}
+
+impl Default for RpcClientConfig {
+    fn default() -> Self {
+        RpcClientConfig {
+            timeout: Duration::from_secs(30),
+            commitment: CommitmentConfig::default(),
+            headers: Vec::new(),
+        }
+    }
+}
+
+impl RpcClientConfig {
+    /// Adds a header sent with every request.
+    pub fn header(mut self, name: impl Into<String>, value: impl Into<String>) -> Self {
+        self.headers.push((name.into(), value.into()));
+        self
+    }
+
+    /// Builds a client for `url` with these settings. Fails if a header name
+    /// or value isn't valid HTTP.
+    pub fn connect(&self, url: impl Into<String>) -> Result<HttpSource, IndexerError> {
+        let url = url.into();
+        let mut headers = HttpSender::default_headers();
+        for (name, value) in &self.headers {
+            let name =
+                HeaderName::from_bytes(name.as_bytes()).map_err(|_| IndexerError::InvalidHeader(name.clone()))?;
+            let value = HeaderValue::from_str(value).map_err(|_| IndexerError::InvalidHeader(name.to_string()))?;
+            headers.insert(name, value);
+        }
+        let http = reqwest::Client::builder()
+            .default_headers(headers)
+            .timeout(self.timeout)
+            .pool_idle_timeout(self.timeout)
+            .build()
+            .map_err(ClientError::from)?;
+
+        let sender = HttpSender::new_with_client(&url, http.clone());
+        let rpc = RpcClient::new_sender(sender, SenderConfig::with_commitment(self.commitment));
+        Ok(HttpSource { rpc, http, url })
+    }
+}
+
+/// An [`RpcClient`] built from an [`RpcClientConfig`]. Batched
+/// `getTransaction` requests go through the same HTTP client, so they carry
+/// the configured headers and timeout too.
+pub struct HttpSource {
+    rpc: RpcClient,
+    http: reqwest::Client,
+    url: String,
+}
+
+impl HttpSource {
+    /// The underlying client, for calls the indexer doesn't make.
+    pub fn rpc_client(&self) -> &RpcClient {
+        &self.rpc
+    }
+}
+
+#[async_trait]
+impl SolanaSource for HttpSource {
+    async fn get_signatures_for_address_with_config(
+        &self,
+        address: &Pubkey,
+        config: GetConfirmedSignaturesForAddress2Config,
+    ) -> ClientResult<Vec<RpcConfirmedTransactionStatusWithSignature>> {
+        self.rpc.get_signatures_for_address_with_config(address, config).await
+    }
+
+    async fn get_transaction_with_config(
+        &self,
+        signature: &Signature,
+        config: RpcTransactionConfig,
+    ) -> ClientResult<EncodedConfirmedTransactionWithStatusMeta> {
+        self.rpc.get_transaction_with_config(signature, config).await
+    }
+
+    #[allow(clippy::result_large_err)]
+    async fn get_transactions_with_config(
+        &self,
+        signatures: &[Signature],
+        config: RpcTransactionConfig,
+    ) -> Vec<ClientResult<EncodedConfirmedTransactionWithStatusMeta>> {
+        match send_transaction_batch(&self.http, &self.url, signatures, config).await {
+            Ok(results) => results,
+            Err(e) => batch_failure(&e, signatures.len()),
+        }
+    }
+
+    async fn get_signature_statuses_with_history(
+        &self,
+        signatures: &[Signature],
+    ) -> RpcResult<Vec<Option<TransactionStatus>>> {
+        self.rpc.get_signature_statuses_with_history(signatures).await
+    }
+
+    async fn get_account_with_commitment(
+        &self,
+        pubkey: &Pubkey,
+        commitment: CommitmentConfig,
+    ) -> RpcResult<Option<Account>> {
+        self.rpc.get_account_with_commitment(pubkey, commitment).await
+    }
+
+    async fn get_slot_with_commitment(&self, commitment: CommitmentConfig) -> ClientResult<Slot> {
+        self.rpc.get_slot_with_commitment(commitment).await
+    }
+
+    async fn get_block_time(&self, slot: Slot) -> ClientResult<UnixTimestamp> {
+        self.rpc.get_block_time(slot).await
+    }
+
+    async fn get_block_with_config(&self, slot: Slot, config: RpcBlockConfig) -> ClientResult<UiConfirmedBlock> {
+        self.rpc.get_block_with_config(slot, config).await
+    }
+}
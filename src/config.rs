@@ -0,0 +1,384 @@
+use chrono::Duration;
+use solana_sdk::{commitment_config::CommitmentConfig, pubkey, pubkey::Pubkey, signature::Signature};
+use std::sync::Arc;
+use tokio_util::sync::CancellationToken;
+
+use crate::error::IndexerError;
+use crate::indexer::{
+    count_signatures_in_range, index_signature_window, index_token_transfers, index_via_associated_accounts,
+    CommitmentFallback, IndexResult, ParseMode, ProgressEvent, TransferFilter, DEFAULT_SIGNATURE_PAGE_LIMIT,
+};
+use crate::mint::validate_mint;
+use crate::models::TransferType;
+use crate::rate_limit::{RateLimitedSource, RateLimiter};
+use crate::retry::RetryPolicy;
+use crate::source::SolanaSource;
+use crate::time::{Clock, TimeRange};
+
+const USDC_MINT: Pubkey = pubkey!("EPjFWdd5AufqSSqeM2qN1xzybapC8G4wEGGkZwyTDt1v");
+
+/// Builder for an indexing run.
+///
+/// Defaults to USDC over the last 96 hours at `confirmed` commitment, eight
+/// concurrent unbatched fetches and failed transactions skipped, so
+/// `IndexerConfig::new(wallet).run(&client)` works as is.
+#[derive(Clone, Debug)]
+pub struct IndexerConfig {
+    wallet: String,
+    mints: Vec<Pubkey>,
+    range: TimeRange,
+    commitment: CommitmentConfig,
+    page_limit: usize,
+    concurrency: usize,
+    batch_size: usize,
+    retry: RetryPolicy,
+    filter: TransferFilter,
+    validate_mints: bool,
+    rate_limiter: Option<RateLimiter>,
+    signature_window: Option<(Option<Signature>, Option<Signature>)>,
+    scan_associated_accounts: bool,
+}
+
+impl IndexerConfig {
+    pub fn new(wallet: impl Into<String>) -> Self {
+        IndexerConfig {
+            wallet: wallet.into(),
+            mints: vec![USDC_MINT],
+            range: TimeRange::last_days(4),
+            commitment: CommitmentConfig::confirmed(),
+            page_limit: DEFAULT_SIGNATURE_PAGE_LIMIT,
+            concurrency: 8,
+            batch_size: 1,
+            retry: RetryPolicy::default(),
+            filter: TransferFilter::default(),
+            validate_mints: false,
+            rate_limiter: None,
+            signature_window: None,
+            scan_associated_accounts: false,
+        }
+    }
+
+    pub fn mints(mut self, mints: impl IntoIterator<Item = Pubkey>) -> Self {
+        self.mints = mints.into_iter().collect();
+        self
+    }
+
+    /// Tracks `mints` as one logical asset named `label`, adding any not
+    /// already tracked. Transfers keep their actual mint and carry `label`
+    /// as their `asset`, so [`crate::summary::summarize_by_asset`] totals
+    /// them together. Use this when a token has migrated to a new mint.
+    pub fn asset(mut self, label: impl Into<String>, mints: impl IntoIterator<Item = Pubkey>) -> Self {
+        let label = label.into();
+        for mint in mints {
+            if !self.mints.contains(&mint) {
+                self.mints.push(mint);
+            }
+            self.filter.asset_labels.insert(mint, label.clone());
+        }
+        self
+    }
+
+    pub fn time_range(mut self, range: TimeRange) -> Self {
+        self.range = range;
+        self
+    }
+
+    /// Bounds the run by signatures instead of time, replacing any time
+    /// range. See [`index_signature_window`] for how `before` and `until`
+    /// apply.
+    pub fn signature_window(mut self, before: Option<Signature>, until: Option<Signature>) -> Self {
+        self.signature_window = Some((before, until));
+        self
+    }
+
+    pub fn commitment(mut self, commitment: CommitmentConfig) -> Self {
+        self.commitment = commitment;
+        self
+    }
+
+    /// Signatures requested per page, at most
+    /// [`DEFAULT_SIGNATURE_PAGE_LIMIT`]. Lower it for providers that cap
+    /// pages below the RPC spec's limit.
+    pub fn page_limit(mut self, page_limit: usize) -> Self {
+        self.page_limit = page_limit;
+        self
+    }
+
+    pub fn concurrency(mut self, concurrency: usize) -> Self {
+        self.concurrency = concurrency;
+        self
+    }
+
+    /// Fetches transactions `batch_size` at a time in one JSON-RPC batch
+    /// request. Only worth raising on endpoints that support batching.
+    pub fn batch_size(mut self, batch_size: usize) -> Self {
+        self.batch_size = batch_size;
+        self
+    }
+
+    pub fn retry(mut self, retry: RetryPolicy) -> Self {
+        self.retry = retry;
+        self
+    }
+
+    /// Drops transfers of `mint` smaller than `amount`.
+    pub fn min_amount(mut self, mint: Pubkey, amount: f64) -> Self {
+        self.filter.min_amounts.insert(mint, amount);
+        self
+    }
+
+    /// Keeps only transfers whose amount lies within `min..=max`; `None`
+    /// leaves that side open.
+    pub fn amount_range(mut self, min: Option<f64>, max: Option<f64>) -> Self {
+        self.filter.amount_filter = (min, max);
+        self
+    }
+
+    pub fn include_failed(mut self, include: bool) -> Self {
+        self.filter.include_failed = include;
+        self
+    }
+
+    pub fn track_wsol_wrapping(mut self, track: bool) -> Self {
+        self.filter.track_wsol_wrapping = track;
+        self
+    }
+
+    /// Refuses to fetch more than `max` transactions in one run. See
+    /// [`TransferFilter::max_transactions`].
+    pub fn max_transactions(mut self, max: usize) -> Self {
+        self.filter.max_transactions = Some(max);
+        self
+    }
+
+    /// Turns every skipped or ambiguous case into an error, for
+    /// reconciliation-grade runs. See [`TransferFilter::strict`].
+    pub fn strict(mut self, strict: bool) -> Self {
+        self.filter.strict = strict;
+        self
+    }
+
+    /// Fetches at most `max_pages` signature pages, newest first, for a quick
+    /// look at recent activity. See [`TransferFilter::max_pages`].
+    pub fn max_pages(mut self, max: usize) -> Self {
+        self.filter.max_pages = Some(max);
+        self
+    }
+
+    /// Stops fetching transactions once `max` transfers have been collected,
+    /// newest first. See [`TransferFilter::max_transfers`].
+    pub fn max_transfers(mut self, max: usize) -> Self {
+        self.filter.max_transfers = Some(max);
+        self
+    }
+
+    /// Stops the run early when `token` is cancelled, such as from a UI's
+    /// stop button, returning what was found so far. See
+    /// [`TransferFilter::cancellation`].
+    pub fn cancellation(mut self, token: CancellationToken) -> Self {
+        self.filter.cancellation = Some(token);
+        self
+    }
+
+    /// Fetches transactions newer than `max_age` that aren't found at the
+    /// run's commitment at `commitment` instead, such as `confirmed` under a
+    /// `finalized` run. See [`TransferFilter::commitment_fallback`].
+    pub fn commitment_fallback(mut self, max_age: Duration, commitment: CommitmentConfig) -> Self {
+        self.filter.commitment_fallback = Some(CommitmentFallback { max_age, commitment });
+        self
+    }
+
+    /// Takes the current time from `clock`, such as a
+    /// [`crate::time::FixedClock`] in tests. See [`TransferFilter::clock`].
+    pub fn clock(mut self, clock: impl Clock + 'static) -> Self {
+        self.filter.clock = Some(Arc::new(clock));
+        self
+    }
+
+    /// Refetches transactions that come back without token balances using
+    /// another encoding. See [`TransferFilter::encoding_fallback`].
+    pub fn encoding_fallback(mut self, fallback: bool) -> Self {
+        self.filter.encoding_fallback = fallback;
+        self
+    }
+
+    /// Emits balance changes of undetermined ownership as
+    /// [`TransferType::Unknown`]. See [`TransferFilter::include_unknown`].
+    pub fn include_unknown(mut self, include: bool) -> Self {
+        self.filter.include_unknown = include;
+        self
+    }
+
+    /// Keeps only transfers of one direction, such as received payments for
+    /// a receivables report. See [`TransferFilter::direction`].
+    pub fn direction(mut self, direction: Option<TransferType>) -> Self {
+        self.filter.direction = direction;
+        self
+    }
+
+    /// Drops transfers to or from any of `addresses`. See
+    /// [`TransferFilter::denylist`].
+    pub fn denylist(mut self, addresses: impl IntoIterator<Item = Pubkey>) -> Self {
+        self.filter.denylist.extend(addresses);
+        self
+    }
+
+    /// Keeps only transfers to or from one of `addresses`. See
+    /// [`TransferFilter::allowlist`].
+    pub fn allowlist(mut self, addresses: impl IntoIterator<Item = Pubkey>) -> Self {
+        self.filter.allowlist = Some(addresses.into_iter().collect());
+        self
+    }
+
+    /// Keeps zero-amount transfers so accounts touched without a balance
+    /// change still show up. See [`TransferFilter::include_zero_amount`].
+    pub fn include_zero_amount(mut self, include: bool) -> Self {
+        self.filter.include_zero_amount = include;
+        self
+    }
+
+    /// Reads transfers from token balance changes (the default) or from
+    /// individual transfer instructions. See [`ParseMode`].
+    pub fn parse_mode(mut self, mode: ParseMode) -> Self {
+        self.filter.parse_mode = mode;
+        self
+    }
+
+    /// Pages the wallet's associated token accounts instead of the wallet,
+    /// skipping transactions that don't touch a tracked mint. Ignored for
+    /// signature windows. See [`index_via_associated_accounts`].
+    pub fn scan_associated_accounts(mut self, scan: bool) -> Self {
+        self.scan_associated_accounts = scan;
+        self
+    }
+
+    /// Checks each mint with [`validate_mint`] before indexing. Off by
+    /// default since it costs an extra lookup per mint.
+    pub fn validate_mints(mut self, validate: bool) -> Self {
+        self.validate_mints = validate;
+        self
+    }
+
+    /// Draws a permit from `limiter` before every RPC call. Pass clones of
+    /// one limiter to several configs to keep them within a shared budget.
+    pub fn rate_limiter(mut self, limiter: RateLimiter) -> Self {
+        self.rate_limiter = Some(limiter);
+        self
+    }
+
+    /// Counts the in-range signatures a [`IndexerConfig::run`] over the
+    /// configured time range would fetch, without fetching them. See
+    /// [`crate::indexer::count_in_range`].
+    pub async fn count_in_range(&self, client: &(impl SolanaSource + Sync)) -> Result<usize, IndexerError> {
+        match &self.rate_limiter {
+            Some(limiter) => {
+                let client = RateLimitedSource::new(client, limiter.clone());
+                self.count_with_source(&client).await
+            }
+            None => self.count_with_source(client).await,
+        }
+    }
+
+    async fn count_with_source(&self, client: &(impl SolanaSource + Sync)) -> Result<usize, IndexerError> {
+        count_signatures_in_range(
+            client,
+            &self.wallet,
+            self.range.start(),
+            self.range.end(),
+            self.page_limit,
+            &self.retry,
+            self.commitment,
+        )
+        .await
+    }
+
+    pub async fn run(&self, client: &(impl SolanaSource + Sync)) -> Result<IndexResult, IndexerError> {
+        self.run_inner(client, None).await
+    }
+
+    /// Like [`IndexerConfig::run`], reporting each [`ProgressEvent`] to
+    /// `progress` as the run advances.
+    pub async fn run_with_progress(
+        &self,
+        client: &(impl SolanaSource + Sync),
+        progress: &(dyn Fn(ProgressEvent) + Sync),
+    ) -> Result<IndexResult, IndexerError> {
+        self.run_inner(client, Some(progress)).await
+    }
+
+    async fn run_inner(
+        &self,
+        client: &(impl SolanaSource + Sync),
+        progress: Option<&(dyn Fn(ProgressEvent) + Sync)>,
+    ) -> Result<IndexResult, IndexerError> {
+        match &self.rate_limiter {
+            Some(limiter) => {
+                let client = RateLimitedSource::new(client, limiter.clone());
+                self.run_with_source(&client, progress).await
+            }
+            None => self.run_with_source(client, progress).await,
+        }
+    }
+
+    async fn run_with_source(
+        &self,
+        client: &(impl SolanaSource + Sync),
+        progress: Option<&(dyn Fn(ProgressEvent) + Sync)>,
+    ) -> Result<IndexResult, IndexerError> {
+        if self.validate_mints {
+            for mint in &self.mints {
+                validate_mint(client, mint, &self.retry, self.commitment).await?;
+            }
+        }
+
+        if let Some((before, until)) = self.signature_window {
+            return index_signature_window(
+                client,
+                &self.wallet,
+                &self.mints,
+                before,
+                until,
+                self.page_limit,
+                self.concurrency,
+                self.batch_size,
+                self.retry,
+                &self.filter,
+                self.commitment,
+                progress,
+            )
+            .await;
+        }
+
+        if self.scan_associated_accounts {
+            return index_via_associated_accounts(
+                client,
+                &self.wallet,
+                &self.mints,
+                self.range,
+                self.page_limit,
+                self.concurrency,
+                self.batch_size,
+                self.retry,
+                &self.filter,
+                self.commitment,
+                progress,
+            )
+            .await;
+        }
+
+        index_token_transfers(
+            client,
+            &self.wallet,
+            &self.mints,
+            self.range,
+            self.page_limit,
+            self.concurrency,
+            self.batch_size,
+            self.retry,
+            &self.filter,
+            self.commitment,
+            progress,
+        )
+        .await
+    }
+}
@@ -0,0 +1,82 @@
+use chrono::{DateTime, Utc};
+use solana_client::client_error::ClientError;
+use std::fmt;
+
+#[derive(Debug)]
+pub enum IndexerError {
+    InvalidPubkey(String),
+    Rpc(Box<ClientError>),
+    InvalidTimestamp(i64),
+    SignatureParse(String),
+    Io(std::io::Error),
+    CheckpointUnavailable(String),
+    PriceUnavailable(String),
+    Json(serde_json::Error),
+    InvalidDateTime(String),
+    InvalidTimeRange(DateTime<Utc>, DateTime<Utc>),
+    NotAMint(String),
+    TooManyTransactions { found: usize, limit: usize },
+    StrictViolation(String),
+    InvalidHeader(String),
+    HistoryUnavailable { oldest_available: DateTime<Utc> },
+}
+
+impl fmt::Display for IndexerError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            IndexerError::InvalidPubkey(key) => write!(f, "Invalid pubkey: {}", key),
+            IndexerError::Rpc(e) => write!(f, "RPC error: {}", e),
+            IndexerError::InvalidTimestamp(t) => write!(f, "Invalid timestamp: {}", t),
+            IndexerError::SignatureParse(sig) => write!(f, "Invalid signature: {}", sig),
+            IndexerError::Io(e) => write!(f, "I/O error: {}", e),
+            IndexerError::CheckpointUnavailable(sig) => {
+                write!(f, "Checkpoint signature {} is no longer available on the node", sig)
+            }
+            IndexerError::PriceUnavailable(mint) => write!(f, "No USD price available for mint {}", mint),
+            IndexerError::Json(e) => write!(f, "JSON error: {}", e),
+            IndexerError::InvalidDateTime(value) => write!(f, "Invalid date or time: {}", value),
+            IndexerError::InvalidTimeRange(start, end) => {
+                write!(f, "Invalid time range: start {} is after end {}", start, end)
+            }
+            IndexerError::NotAMint(key) => write!(f, "{} is not a token mint account", key),
+            IndexerError::TooManyTransactions { found, limit } => {
+                write!(f, "Found {} transactions to fetch, over the limit of {}", found, limit)
+            }
+            IndexerError::StrictViolation(detail) => write!(f, "Strict mode: {}", detail),
+            IndexerError::InvalidHeader(name) => write!(f, "Invalid HTTP header: {}", name),
+            IndexerError::HistoryUnavailable { oldest_available } => {
+                write!(f, "RPC node history only reaches back to {}", oldest_available)
+            }
+        }
+    }
+}
+
+impl std::error::Error for IndexerError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            IndexerError::Rpc(e) => Some(e.as_ref()),
+            IndexerError::Io(e) => Some(e),
+            IndexerError::Json(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<ClientError> for IndexerError {
+    fn from(e: ClientError) -> Self {
+        IndexerError::Rpc(Box::new(e))
+    }
+}
+
+
+impl From<std::io::Error> for IndexerError {
+    fn from(e: std::io::Error) -> Self {
+        IndexerError::Io(e)
+    }
+}
+
+impl From<serde_json::Error> for IndexerError {
+    fn from(e: serde_json::Error) -> Self {
+        IndexerError::Json(e)
+    }
+}
\ No newline at end of file
@@ -0,0 +1,119 @@
+use std::io::{Read, Write};
+
+use crate::error::IndexerError;
+use crate::models::{Transfer, TransferType};
+
+/// How many decimal places exported amounts carry.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Precision {
+    /// Amounts as computed, which can show a floating point tail such as
+    /// `0.30000000000000004`.
+    #[default]
+    Full,
+    /// Amounts rounded half to even to their token's decimals, as block
+    /// explorers display them. `amount_raw` is exact either way.
+    TokenDecimals,
+}
+
+impl Precision {
+    /// The amount of `t` to write.
+    pub fn apply(&self, t: &Transfer) -> f64 {
+        match self {
+            Precision::Full => t.amount,
+            Precision::TokenDecimals => round_amount(t.amount, t.decimals),
+        }
+    }
+}
+
+/// Rounds `amount` to `decimals` places, ties to even (banker's rounding).
+pub fn round_amount(amount: f64, decimals: u8) -> f64 {
+    let scale = 10f64.powi(decimals as i32);
+    (amount * scale).round_ties_even() / scale
+}
+
+/// Writes `transfers` as CSV with a `date,amount,type,signature` header.
+/// Dates are RFC3339 and amounts are written in plain decimal notation.
+pub fn export_csv(transfers: &[Transfer], writer: impl Write) -> Result<(), IndexerError> {
+    export_csv_with_precision(transfers, writer, Precision::Full)
+}
+
+/// Like [`export_csv`], writing amounts at `precision`.
+pub fn export_csv_with_precision(
+    transfers: &[Transfer],
+    mut writer: impl Write,
+    precision: Precision,
+) -> Result<(), IndexerError> {
+    writeln!(writer, "date,amount,type,signature")?;
+    for t in transfers {
+        let type_str = match t.transfer_type {
+            TransferType::Sent => "Sent",
+            TransferType::Received => "Received",
+            TransferType::Internal => "Internal",
+            TransferType::Unknown => "Unknown",
+        };
+        writeln!(writer, "{},{},{},{}", t.date.to_rfc3339(), precision.apply(t), type_str, t.signature)?;
+    }
+    writer.flush()?;
+    Ok(())
+}
+
+/// Writes each transfer as a single-line JSON object terminated by `\n`.
+/// An empty slice produces no output.
+pub fn export_jsonl(transfers: &[Transfer], mut writer: impl Write) -> Result<(), IndexerError> {
+    for t in transfers {
+        serde_json::to_writer(&mut writer, t)?;
+        writer.write_all(b"\n")?;
+    }
+    writer.flush()?;
+    Ok(())
+}
+
+/// Reads transfers written by [`export_jsonl`], or a JSON array of them.
+/// Fields added since the file was written are left empty where optional.
+pub fn import_json(mut reader: impl Read) -> Result<Vec<Transfer>, IndexerError> {
+    let mut input = String::new();
+    reader.read_to_string(&mut input)?;
+    if input.trim_start().starts_with('[') {
+        return Ok(serde_json::from_str(&input)?);
+    }
+    serde_json::Deserializer::from_str(&input)
+        .into_iter::<Transfer>()
+        .map(|transfer| transfer.map_err(IndexerError::from))
+        .collect()
+}
+
+/// Renders `transfers` as a QIF bank statement for `account_name`, for
+/// accounting software that imports QIF. Each transfer becomes one
+/// transaction dated `MM/DD/YYYY` (UTC), with sent and internal transfers as
+/// negative amounts. Unknown transfers have no sign and are left out. The
+/// signature is the reference number, the counterparty
+/// the payee and the on-chain memo, if any, the memo. Line breaks in
+/// `account_name` become spaces and a leading `^` or `!` is dropped.
+pub fn export_qif(transfers: &[Transfer], account_name: &str) -> String {
+    export_qif_with_precision(transfers, account_name, Precision::Full)
+}
+
+/// Like [`export_qif`], writing amounts at `precision`.
+pub fn export_qif_with_precision(transfers: &[Transfer], account_name: &str, precision: Precision) -> String {
+    // A line break or a leading `^` or `!` in the name would end the header early
+    let account_name = account_name.replace(['\r', '\n'], " ");
+    let account_name = account_name.trim_start_matches(['^', '!']);
+    let mut qif = format!("!Account\nN{}\nTBank\n^\n!Type:Bank\n", account_name);
+    for t in transfers {
+        let sign = match t.transfer_type {
+            TransferType::Received => "",
+            TransferType::Sent | TransferType::Internal => "-",
+            TransferType::Unknown => continue,
+        };
+        qif.push_str(&format!("D{}\nT{}{}\nN{}\n", t.date.format("%m/%d/%Y"), sign, precision.apply(t), t.signature));
+        if let Some(counterparty) = &t.counterparty {
+            qif.push_str(&format!("P{}\n", counterparty));
+        }
+        // QIF fields are line-based, so a multi-line memo is flattened
+        if let Some(memo) = &t.memo {
+            qif.push_str(&format!("M{}\n", memo.replace(['\r', '\n'], " ")));
+        }
+        qif.push_str("^\n");
+    }
+    qif
+}
@@ -0,0 +1,74 @@
+use chrono::{DateTime, Duration, Utc};
+use serde::Serialize;
+use std::collections::BTreeSet;
+
+use crate::models::{Transfer, TransferType};
+
+/// An expected periodic transfer that didn't arrive. `start` and `end` bound
+/// the tolerance window around `expected`.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct MissingWindow {
+    pub expected: DateTime<Utc>,
+    pub start: DateTime<Utc>,
+    pub end: DateTime<Utc>,
+}
+
+/// Reports the payments missing from a schedule of one received transfer
+/// every `expected_interval`, anchored at the earliest received transfer.
+/// Each received transfer is matched to its nearest scheduled slot and fills
+/// it if it lands within `tolerance`, so early or late payments, and several
+/// payments clustered around one slot, don't raise false gaps. Sent and
+/// internal transfers are ignored; filter by mint beforehand.
+///
+/// Slots after the latest received transfer aren't checked; use
+/// [`detect_gaps_until`] to catch a missed payment at the end of the period.
+pub fn detect_gaps(transfers: &[Transfer], expected_interval: Duration, tolerance: Duration) -> Vec<MissingWindow> {
+    let Some(latest) = received_dates(transfers).max() else {
+        return Vec::new();
+    };
+    detect_gaps_until(transfers, expected_interval, tolerance, latest)
+}
+
+/// Like [`detect_gaps`], also checking every slot whose tolerance window has
+/// closed by `end`.
+pub fn detect_gaps_until(
+    transfers: &[Transfer],
+    expected_interval: Duration,
+    tolerance: Duration,
+    end: DateTime<Utc>,
+) -> Vec<MissingWindow> {
+    let interval_ms = expected_interval.num_milliseconds();
+    let Some(anchor) = received_dates(transfers).min() else {
+        return Vec::new();
+    };
+    if interval_ms <= 0 {
+        return Vec::new();
+    }
+
+    let slot_time = |slot: i64| anchor + Duration::milliseconds(slot * interval_ms);
+    let filled: BTreeSet<i64> = received_dates(transfers)
+        .filter_map(|date| {
+            let offset = (date - anchor).num_milliseconds();
+            let slot = (offset + interval_ms / 2).div_euclid(interval_ms);
+            ((date - slot_time(slot)).abs() <= tolerance).then_some(slot)
+        })
+        .collect();
+
+    (1..)
+        .map(|slot| (slot, slot_time(slot)))
+        .take_while(|(_, expected)| *expected + tolerance <= end)
+        .filter(|(slot, _)| !filled.contains(slot))
+        .map(|(_, expected)| MissingWindow {
+            expected,
+            start: expected - tolerance,
+            end: expected + tolerance,
+        })
+        .collect()
+}
+
+fn received_dates(transfers: &[Transfer]) -> impl Iterator<Item = DateTime<Utc>> + '_ {
+    transfers
+        .iter()
+        .filter(|t| matches!(t.transfer_type, TransferType::Received))
+        .map(|t| t.date)
+}
@@ -1,164 +1,2249 @@
-use chrono::{DateTime, Utc, TimeZone};
-use solana_client::nonblocking::rpc_client::RpcClient;
+use chrono::{DateTime, Duration, Utc, TimeZone};
+use futures::future;
+use futures::stream::{self, Stream, StreamExt, TryStreamExt};
+use solana_client::client_error::ClientError;
+use solana_client::rpc_client::GetConfirmedSignaturesForAddress2Config;
+use solana_client::rpc_config::RpcTransactionConfig;
+use solana_client::rpc_response::RpcConfirmedTransactionStatusWithSignature;
 use solana_sdk::{
+    clock::Slot,
     pubkey::Pubkey,
     signature::Signature,
-    commitment_config::CommitmentConfig,
+    commitment_config::{CommitmentConfig, CommitmentLevel},
 };
-use solana_transaction_status::{UiTransactionEncoding, EncodedConfirmedTransactionWithStatusMeta};
+use solana_transaction_status::{
+    option_serializer::OptionSerializer, UiInstruction, UiParsedInstruction, UiTransactionEncoding,
+    UiTransactionTokenBalance,
+    EncodedConfirmedTransactionWithStatusMeta,
+};
+use std::cell::Cell;
+use std::collections::{HashMap, HashSet};
 use std::str::FromStr;
-use log::{info, warn, error};
+use std::sync::Arc;
+use tokio_util::sync::CancellationToken;
+use tracing::{debug, debug_span, error, info, info_span, trace, warn, Instrument};
+
+use crate::archive::is_unavailable;
+use crate::error::IndexerError;
+use crate::instructions::{
+    account_key, account_keys, balance_change, has_account_instruction, instruction_kind, instructions_with_caller,
+    parse_memo, withheld_fee, WSOL_MINT,
+};
+use crate::mint::{associated_token_address_with_program, TOKEN_2022_PROGRAM_ID};
+use crate::models::{Transfer, TransferType};
+use crate::retry::{retry_with_backoff, RetryPolicy};
+use crate::seek::seek_signature_cursor;
+use crate::source::{CountingSource, SolanaSource};
+use crate::time::{Clock, TimeRange};
+
+/// Default number of signatures requested per `getSignaturesForAddress`
+/// page. This is also the most the RPC spec allows; larger limits are
+/// clamped to it.
+pub const DEFAULT_SIGNATURE_PAGE_LIMIT: usize = 1000;
+
+/// Token instructions that mark a WSOL balance change as wrapping or unwrapping.
+const WSOL_WRAP_INSTRUCTIONS: [&str; 5] = [
+    "syncNative",
+    "closeAccount",
+    "initializeAccount",
+    "initializeAccount2",
+    "initializeAccount3",
+];
+
+/// Transfers found by an indexing run, plus the transactions that could not
+/// be fetched. A few pruned or unavailable transactions don't discard an
+/// otherwise complete scan; callers that need every transaction can check
+/// that `errors` is empty.
+#[derive(Default)]
+pub struct IndexResult {
+    /// Within one signature, sent transfers come before received ones and
+    /// each direction is in account index order, so output is reproducible.
+    pub transfers: Vec<Transfer>,
+    pub errors: Vec<(Signature, IndexerError)>,
+    /// Signatures skipped because neither the signature listing nor
+    /// `getBlockTime` could date them.
+    pub skipped_no_block_time: usize,
+    pub stats: IndexStats,
+}
+
+/// What a run cost, for tuning and tracking RPC spend.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct IndexStats {
+    /// Requests made to the source, a batch counting once. Includes lookups
+    /// a [`crate::cache::CachedSource`] answered, counted in `cache_hits`.
+    pub rpc_calls: usize,
+    /// Signatures listed for the address, before time filtering.
+    pub signatures_fetched: usize,
+    /// Signatures dated inside the requested range.
+    pub in_range: usize,
+    /// In-range transactions whose bodies were fetched successfully.
+    pub transactions_fetched: usize,
+    /// Transactions served from a cache.
+    pub cache_hits: usize,
+    /// Signature paging stopped at [`TransferFilter::max_pages`], or fetching
+    /// at [`TransferFilter::max_transfers`], with older signatures left in
+    /// the range, so the transfers are only the most recent part of it.
+    pub truncated: bool,
+    /// Set when the node's history for the address seems to end after the
+    /// start of the range, to the oldest block time it returned. Transfers
+    /// before it may be missing; see [`IndexerError::HistoryUnavailable`].
+    pub oldest_available: Option<DateTime<Utc>>,
+    /// The run was stopped through [`TransferFilter::cancellation`] before
+    /// it finished, so the transfers are incomplete.
+    pub cancelled: bool,
+}
+
+impl IndexStats {
+    fn add(&mut self, other: &IndexStats) {
+        self.rpc_calls += other.rpc_calls;
+        self.signatures_fetched += other.signatures_fetched;
+        self.in_range += other.in_range;
+        self.transactions_fetched += other.transactions_fetched;
+        self.cache_hits += other.cache_hits;
+        self.truncated |= other.truncated;
+        self.oldest_available = self.oldest_available.max(other.oldest_available);
+        self.cancelled |= other.cancelled;
+    }
+}
 
-#[derive(Debug)]
-pub enum TransferType {
-    Sent,
-    Received,
+/// Progress of an indexing run, reported to the optional callback passed to
+/// [`index_token_transfers`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ProgressEvent {
+    /// Another page of signatures arrived; carries the running total.
+    SignaturesFetched(usize),
+    /// The `index`-th of `total` in-range transactions was fetched (or
+    /// failed to fetch). Transactions complete out of order, so `index` is a
+    /// count rather than a position in the signature list.
+    TransactionProcessed { index: usize, total: usize },
+    /// The run finished with `transfers` transfers.
+    Done { transfers: usize },
 }
 
-#[derive(Debug)]
-pub struct Transfer {
-    pub signature: String,
-    pub timestamp: DateTime<Utc>,
-    pub from: Option<String>,
-    pub to: Option<String>,
-    pub amount: f64,
-    pub transfer_type: TransferType,
+/// Rules applied while extracting transfers from each transaction, so
+/// unwanted balance changes are dropped before they are ever returned.
+#[derive(Clone, Debug, Default)]
+pub struct TransferFilter {
+    /// Keep transfers from transactions that failed on chain, marked with
+    /// `success: false`. Failed transactions are skipped by default.
+    pub include_failed: bool,
+    /// Per-mint minimum absolute amount; smaller changes (dust, spam
+    /// airdrops) are dropped.
+    pub min_amounts: HashMap<Pubkey, f64>,
+    /// Inclusive bounds on the absolute amount of every tracked mint, either
+    /// of which may be open. Applied on top of `min_amounts`, and to native
+    /// SOL runs in whole SOL.
+    pub amount_filter: (Option<f64>, Option<f64>),
+    /// Keep wrapped SOL balance changes caused purely by wrapping or
+    /// unwrapping the wallet's own SOL. These are suppressed by default.
+    pub track_wsol_wrapping: bool,
+    /// How transfers are read out of each transaction.
+    pub parse_mode: ParseMode,
+    /// Fail with [`IndexerError::TooManyTransactions`] before fetching any
+    /// transaction bodies when more in-range signatures than this are found,
+    /// guarding against indexing a high-volume wallet by mistake. Streaming
+    /// runs fetch lazily and don't apply it.
+    pub max_transactions: Option<usize>,
+    /// Stop after this many signature pages, newest first, even if the time
+    /// range reaches further back, for a quick look at recent activity. A
+    /// cut-short run sets [`IndexStats::truncated`]. Streaming runs don't
+    /// apply it.
+    pub max_pages: Option<usize>,
+    /// Stop fetching transactions once this many transfers have been
+    /// collected, to bound the work behind one page of a UI. Transactions
+    /// are then processed newest first and the last one is kept whole, so a
+    /// swap's legs aren't split and slightly more transfers may come back.
+    /// Every older in-range transaction is left unfetched, whether or not it
+    /// holds transfers, and a cut-short run sets [`IndexStats::truncated`];
+    /// the oldest transfer's date is where to resume. Signatures are still
+    /// listed for the whole range. Multi-wallet runs apply it per wallet and
+    /// streaming runs don't apply it.
+    pub max_transfers: Option<usize>,
+    /// Fail the run with [`IndexerError::StrictViolation`] wherever data
+    /// would otherwise be skipped with a log line: signatures without a block
+    /// time, transactions without status meta or token balances, token
+    /// balances whose owner can't be determined, and signature history that
+    /// seems to stop short of the range start.
+    pub strict: bool,
+    /// Emit zero-amount transfers for tracked token accounts of the wallet
+    /// that a transaction touched without changing their balance (or, with
+    /// [`ParseMode::Instructions`], for zero-amount transfer instructions).
+    /// Balance entries carry no direction and are recorded as received.
+    pub include_zero_amount: bool,
+    /// Emit balance changes whose owner can't be determined (balances
+    /// recorded before the owner field existed, outside the wallet's
+    /// associated accounts) as [`TransferType::Unknown`] for manual review,
+    /// instead of skipping them.
+    pub include_unknown: bool,
+    /// Keep only transfers of this direction. `None` keeps every direction.
+    /// The other side is still read from the same transaction, so this saves
+    /// no RPC calls.
+    pub direction: Option<TransferType>,
+    /// Drop transfers whose counterparty wallet or token account is one of
+    /// these, such as known spam or dust senders. Transfers without a
+    /// resolved counterparty are kept.
+    pub denylist: HashSet<Pubkey>,
+    /// Keep only transfers whose counterparty wallet or token account is one
+    /// of these, dropping those without a resolved counterparty. `None`
+    /// keeps every counterparty. Applied after `denylist`.
+    pub allowlist: Option<HashSet<Pubkey>>,
+    /// Logical asset label per mint, copied to each transfer's `asset`.
+    /// Giving an old and a new mint the same label lets summaries roll a
+    /// migrated token's history up together.
+    pub asset_labels: HashMap<Pubkey, String>,
+    /// Stops the run early once cancelled, between signature pages and
+    /// between transactions. The transfers found so far are returned with
+    /// [`IndexStats::cancelled`] set; a stream just ends.
+    pub cancellation: Option<CancellationToken>,
+    /// Lists signatures at a lower commitment and fetches transactions too
+    /// recent to be found at the requested one at that commitment instead,
+    /// marking their transfers with it. Streaming runs apply it too.
+    pub commitment_fallback: Option<CommitmentFallback>,
+    /// Refetch transactions whose `JsonParsed` body has no token balances
+    /// with the plain `Json` encoding, whose account keys and balances
+    /// balance mode reads just as well. Costs a request per such
+    /// transaction, including old ones that predate token balances.
+    pub encoding_fallback: bool,
+    /// Current time for `commitment_fallback`'s age check, so tests can pin
+    /// it. `None` uses the system clock.
+    pub clock: Option<Arc<dyn Clock>>,
 }
 
-pub async fn index_usdc_transfers(
-    client: &RpcClient,
+/// Commitment to fall back to for transactions newer than `max_age`, which
+/// may not have reached the requested commitment yet. With `confirmed`
+/// under a `finalized` run, a near-real-time scan still sees the newest
+/// transfers, marked as `confirmed` until a later run finds them final.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct CommitmentFallback {
+    pub max_age: Duration,
+    pub commitment: CommitmentConfig,
+}
+
+/// Source of the transfers extracted from a transaction.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ParseMode {
+    /// One transfer per token account whose balance changed, from the pre
+    /// and post token balances. Works for any program that moves tokens, but
+    /// several transfers to one account within a transaction net to one.
+    #[default]
+    BalanceDeltas,
+    /// One transfer per `transfer`, `transferChecked` or
+    /// `transferCheckedWithFee` instruction of the token programs, inner
+    /// instructions included. Keeps the legs of batched payouts apart, but
+    /// misses movements that aren't token transfers, such as mints and burns.
+    Instructions,
+}
+
+impl TransferFilter {
+    pub(crate) fn check_transaction_cap(&self, found: usize) -> Result<(), IndexerError> {
+        match self.max_transactions {
+            Some(limit) if found > limit => {
+                warn!("Found {} in-range signatures, more than the cap of {}", found, limit);
+                Err(IndexerError::TooManyTransactions { found, limit })
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Whether `amount` of `mint` falls below its minimum or outside
+    /// `amount_filter`.
+    pub(crate) fn excludes_amount(&self, mint: &str, amount: f64) -> bool {
+        let (min, max) = self.amount_filter;
+        min.is_some_and(|min| amount < min) || max.is_some_and(|max| amount > max) || self.below_minimum(mint, amount)
+    }
+
+    pub(crate) fn excludes_direction(&self, transfer_type: &TransferType) -> bool {
+        self.direction.as_ref().is_some_and(|direction| direction != transfer_type)
+    }
+
+    /// Whether `t`'s counterparty is denied, or missing from the allowlist.
+    pub(crate) fn excludes_counterparty(&self, t: &Transfer) -> bool {
+        if self.denylist.is_empty() && self.allowlist.is_none() {
+            return false;
+        }
+        let addresses: Vec<Pubkey> = [&t.counterparty, &t.counterparty_token_account]
+            .into_iter()
+            .flatten()
+            .filter_map(|address| Pubkey::from_str(address).ok())
+            .collect();
+        addresses.iter().any(|address| self.denylist.contains(address))
+            || self
+                .allowlist
+                .as_ref()
+                .is_some_and(|allowed| !addresses.iter().any(|address| allowed.contains(address)))
+    }
+
+    pub(crate) fn is_cancelled(&self) -> bool {
+        self.cancellation.as_ref().is_some_and(CancellationToken::is_cancelled)
+    }
+
+    /// Resolves once the run is cancelled; never, without a token.
+    pub(crate) async fn cancelled(&self) {
+        match &self.cancellation {
+            Some(token) => token.cancelled().await,
+            None => future::pending().await,
+        }
+    }
+
+    pub(crate) fn now(&self) -> DateTime<Utc> {
+        self.clock.as_ref().map_or_else(Utc::now, |clock| clock.now())
+    }
+
+    /// Commitment to list signatures at, low enough to include the recent
+    /// ones a fallback may fetch.
+    pub(crate) fn listing_commitment(&self, commitment: CommitmentConfig) -> CommitmentConfig {
+        self.commitment_fallback.map_or(commitment, |fallback| fallback.commitment)
+    }
+
+    /// Accepts a skipped case, or rejects it in strict mode.
+    pub(crate) fn lenient(&self, signature: &Signature, reason: &str) -> Result<(), IndexerError> {
+        if self.strict {
+            return Err(IndexerError::StrictViolation(format!("{}: {}", signature, reason)));
+        }
+        Ok(())
+    }
+
+    /// Warns when the node's history seems to end inside `range`, failing
+    /// in strict mode.
+    pub(crate) fn check_history(
+        &self,
+        oldest_available: Option<DateTime<Utc>>,
+        range: TimeRange,
+    ) -> Result<(), IndexerError> {
+        let Some(oldest_available) = oldest_available else {
+            return Ok(());
+        };
+        warn!(
+            "Signatures end at {}, after the range start {}; the node may have pruned older history",
+            oldest_available,
+            range.start()
+        );
+        if self.strict {
+            return Err(IndexerError::HistoryUnavailable { oldest_available });
+        }
+        Ok(())
+    }
+
+    pub(crate) fn check_block_times(&self, skipped_no_block_time: usize) -> Result<(), IndexerError> {
+        if self.strict && skipped_no_block_time > 0 {
+            return Err(IndexerError::StrictViolation(format!(
+                "{} signatures have no block time",
+                skipped_no_block_time
+            )));
+        }
+        Ok(())
+    }
+
+    fn below_minimum(&self, mint: &str, amount: f64) -> bool {
+        Pubkey::from_str(mint)
+            .ok()
+            .and_then(|mint| self.min_amounts.get(&mint))
+            .is_some_and(|min| amount < *min)
+    }
+}
+
+/// Indexes balance changes for every mint in `mints` over a single pass of
+/// the wallet's signatures, tagging each transfer with the mint it belongs to.
+/// Only transactions dated within `range` are indexed. Signature paging
+/// starts at a block just after its end rather than at the chain head, so
+/// windows deep in a long history don't page through everything newer
+/// first.
+///
+/// Signatures are paged `page_limit` at a time, clamped to
+/// [`DEFAULT_SIGNATURE_PAGE_LIMIT`]; lower it for providers that reject full
+/// pages. Up to `concurrency` requests are in flight at once. When
+/// `batch_size` is above one, each request is a JSON-RPC batch of that many
+/// `getTransaction` calls, and any transaction missing from a batch reply is
+/// fetched again on its own. A transaction that still can't be fetched after
+/// retrying transient RPC failures according to `retry` is recorded in
+/// [`IndexResult::errors`] rather than aborting the run. `filter` decides
+/// which balance changes become transfers (see [`TransferFilter`]). Both
+/// signatures and transactions are fetched at `commitment`. Transfers are
+/// returned sorted by date, then signature.
+///
+/// If `progress` is given it receives a [`ProgressEvent`] as each signature
+/// page arrives, as each transaction completes and once at the end.
+///
+/// The run is traced in an `index_token_transfers` span carrying `wallet`,
+/// with a `process_transaction` span per transaction carrying `signature`
+/// and `slot`. Without a `tracing` subscriber, events go to `log` instead.
+#[allow(clippy::too_many_arguments)]
+pub async fn index_token_transfers(
+    client: &(impl SolanaSource + Sync),
+    wallet: &str,
+    mints: &[Pubkey],
+    range: TimeRange,
+    page_limit: usize,
+    concurrency: usize,
+    batch_size: usize,
+    retry: RetryPolicy,
+    filter: &TransferFilter,
+    commitment: CommitmentConfig,
+    progress: Option<&(dyn Fn(ProgressEvent) + Sync)>,
+) -> Result<IndexResult, IndexerError> {
+    let span = info_span!("index_token_transfers", wallet);
+    async {
+        let client = &CountingSource::new(client);
+        let hits_before = client.cache_hits();
+        let wallet_pubkey =
+            Pubkey::from_str(wallet).map_err(|_| IndexerError::InvalidPubkey(wallet.to_string()))?;
+
+        info!(wallet, "Fetching signatures for wallet");
+        let before = seek_signature_cursor(client, range.end(), &retry, commitment).await;
+        let listing = fetch_signature_pages(
+            client,
+            &wallet_pubkey,
+            range.start(),
+            before,
+            page_limit,
+            filter,
+            &retry,
+            commitment,
+            progress,
+        )
+        .await?;
+        filter.check_history(listing.oldest_available, range)?;
+        let signatures = listing.signatures;
+
+        info!(signatures = signatures.len(), "Found signatures");
+        let (in_range, skipped_no_block_time) =
+            in_range_signatures(client, &signatures, range.start(), range.end(), &retry).await?;
+        filter.check_transaction_cap(in_range.len())?;
+        filter.check_block_times(skipped_no_block_time)?;
+
+        let mut result = fetch_and_process(
+            client,
+            in_range,
+            concurrency,
+            batch_size,
+            retry,
+            commitment,
+            progress,
+            filter,
+            |tx, tx_time, signature| process_transaction(tx, &wallet_pubkey, mints, tx_time, signature, filter),
+        )
+        .await?;
+        result.skipped_no_block_time = skipped_no_block_time;
+        result.stats.signatures_fetched = signatures.len();
+        result.stats.rpc_calls = client.calls();
+        result.stats.cache_hits = client.cache_hits() - hits_before;
+        result.stats.truncated |= listing.truncated;
+        result.stats.oldest_available = listing.oldest_available;
+        result.stats.cancelled |= listing.cancelled;
+
+        info!(
+            transfers = result.transfers.len(),
+            failed_fetches = result.errors.len(),
+            "Returning transfers"
+        );
+        if let Some(progress) = progress {
+            progress(ProgressEvent::Done { transfers: result.transfers.len() });
+        }
+        Ok(result)
+    }
+    .instrument(span)
+    .await
+}
+
+/// Indexes the balance changes of one token account of `mint`, paging the
+/// token account's own signatures rather than its owner's, so unrelated
+/// activity of the owning wallet is never fetched. Each transaction's
+/// balances for the account name its owner, recorded as the transfer's
+/// `wallet`. Other parameters behave as they do for
+/// [`index_token_transfers`].
+#[allow(clippy::too_many_arguments)]
+pub async fn index_for_token_account(
+    client: &(impl SolanaSource + Sync),
+    token_account: &Pubkey,
+    mint: &Pubkey,
+    range: TimeRange,
+    page_limit: usize,
+    concurrency: usize,
+    batch_size: usize,
+    retry: RetryPolicy,
+    filter: &TransferFilter,
+    commitment: CommitmentConfig,
+) -> Result<IndexResult, IndexerError> {
+    let client = &CountingSource::new(client);
+    let hits_before = client.cache_hits();
+    info!("Fetching signatures for token account: {}", token_account);
+    let before = seek_signature_cursor(client, range.end(), &retry, commitment).await;
+    let listing = fetch_signature_pages(
+        client,
+        token_account,
+        range.start(),
+        before,
+        page_limit,
+        filter,
+        &retry,
+        commitment,
+        None,
+    )
+    .await?;
+    filter.check_history(listing.oldest_available, range)?;
+    let signatures = listing.signatures;
+
+    info!("Found {} signatures", signatures.len());
+    let (in_range, skipped_no_block_time) =
+        in_range_signatures(client, &signatures, range.start(), range.end(), &retry).await?;
+    filter.check_transaction_cap(in_range.len())?;
+    filter.check_block_times(skipped_no_block_time)?;
+
+    let account = token_account.to_string();
+    let mints = std::slice::from_ref(mint);
+    let mut result = fetch_and_process(
+        client,
+        in_range,
+        concurrency,
+        batch_size,
+        retry,
+        commitment,
+        None,
+        filter,
+        |tx, tx_time, signature| {
+            let Some(owner) = token_account_owner(tx, &account) else {
+                filter.lenient(signature, "token account owner unknown")?;
+                return Ok(Vec::new());
+            };
+            let mut transfers = process_transaction(tx, &owner, mints, tx_time, signature, filter)?;
+            transfers.retain(|t| t.token_account == account);
+            Ok(transfers)
+        },
+    )
+    .await?;
+    result.skipped_no_block_time = skipped_no_block_time;
+    result.stats.signatures_fetched = signatures.len();
+    result.stats.rpc_calls = client.calls();
+    result.stats.cache_hits = client.cache_hits() - hits_before;
+    result.stats.truncated |= listing.truncated;
+    result.stats.oldest_available = listing.oldest_available;
+    result.stats.cancelled |= listing.cancelled;
+
+    info!("Returning {} transfers ({} failed fetches)", result.transfers.len(), result.errors.len());
+    Ok(result)
+}
+
+/// Indexes `wallet` through its associated token accounts instead of the
+/// wallet itself: for each mint the wallet's ATA under SPL Token and
+/// Token-2022 is paged, so transactions that never touch a tracked mint
+/// aren't fetched. The accounts' signatures are merged first, so a
+/// transaction touching several of them is fetched once. Transfers through token
+/// accounts other than the ATAs are missed. If some mint has no ATA (never
+/// created, or closed since), the whole run falls back to
+/// [`index_token_transfers`] over the wallet. Other parameters behave as they
+/// do there.
+#[allow(clippy::too_many_arguments)]
+pub async fn index_via_associated_accounts(
+    client: &(impl SolanaSource + Sync),
+    wallet: &str,
+    mints: &[Pubkey],
+    range: TimeRange,
+    page_limit: usize,
+    concurrency: usize,
+    batch_size: usize,
+    retry: RetryPolicy,
+    filter: &TransferFilter,
+    commitment: CommitmentConfig,
+    progress: Option<&(dyn Fn(ProgressEvent) + Sync)>,
+) -> Result<IndexResult, IndexerError> {
+    let client = &CountingSource::new(client);
+    let hits_before = client.cache_hits();
+    let wallet_pubkey =
+        Pubkey::from_str(wallet).map_err(|_| IndexerError::InvalidPubkey(wallet.to_string()))?;
+
+    let mut accounts = Vec::new();
+    for mint in mints {
+        let mut found = false;
+        for token_program in [spl_token::id(), TOKEN_2022_PROGRAM_ID] {
+            let ata = associated_token_address_with_program(&wallet_pubkey, mint, &token_program);
+            let account = retry_with_backoff(&retry, || client.get_account_with_commitment(&ata, commitment))
+                .await?
+                .value;
+            if account.is_some() {
+                accounts.push((ata, *mint));
+                found = true;
+            }
+        }
+        if !found {
+            info!("No associated token account of {} for mint {}, scanning the wallet", wallet, mint);
+            let mut result = index_token_transfers(
+                client,
+                wallet,
+                mints,
+                range,
+                page_limit,
+                concurrency,
+                batch_size,
+                retry,
+                filter,
+                commitment,
+                progress,
+            )
+            .await?;
+            // Count the account lookups made before falling back as well
+            result.stats.rpc_calls = client.calls();
+            return Ok(result);
+        }
+    }
+
+    // A transaction touching several of the accounts, such as a swap between two tracked
+    // mints, is listed once per account but only fetched and processed once
+    let mut signatures = Vec::new();
+    let mut seen = HashSet::new();
+    let mut truncated = false;
+    let mut oldest_available = None;
+    let mut cancelled = false;
+    let before = seek_signature_cursor(client, range.end(), &retry, commitment).await;
+    for (token_account, _) in &accounts {
+        info!("Fetching signatures for token account: {}", token_account);
+        let listing = fetch_signature_pages(
+            client,
+            token_account,
+            range.start(),
+            before,
+            page_limit,
+            filter,
+            &retry,
+            commitment,
+            None,
+        )
+        .await?;
+        filter.check_history(listing.oldest_available, range)?;
+        truncated |= listing.truncated;
+        oldest_available = oldest_available.max(listing.oldest_available);
+        cancelled |= listing.cancelled;
+        signatures.extend(
+            listing
+                .signatures
+                .into_iter()
+                .filter(|sig_info| seen.insert(sig_info.signature.clone())),
+        );
+    }
+    if let Some(progress) = progress {
+        progress(ProgressEvent::SignaturesFetched(signatures.len()));
+    }
+
+    info!("Found {} signatures across {} accounts", signatures.len(), accounts.len());
+    let (in_range, skipped_no_block_time) =
+        in_range_signatures(client, &signatures, range.start(), range.end(), &retry).await?;
+    filter.check_transaction_cap(in_range.len())?;
+    filter.check_block_times(skipped_no_block_time)?;
+
+    let account_keys: HashSet<String> = accounts.iter().map(|(account, _)| account.to_string()).collect();
+    let mut result = fetch_and_process(
+        client,
+        in_range,
+        concurrency,
+        batch_size,
+        retry,
+        commitment,
+        progress,
+        filter,
+        |tx, tx_time, signature| {
+            let mut transfers = process_transaction(tx, &wallet_pubkey, mints, tx_time, signature, filter)?;
+            transfers.retain(|t| account_keys.contains(&t.token_account));
+            Ok(transfers)
+        },
+    )
+    .await?;
+    sort_transfers(&mut result.transfers);
+    result.skipped_no_block_time = skipped_no_block_time;
+    result.stats.signatures_fetched = signatures.len();
+    result.stats.rpc_calls = client.calls();
+    result.stats.cache_hits = client.cache_hits() - hits_before;
+    result.stats.truncated |= truncated;
+    result.stats.oldest_available = oldest_available;
+    result.stats.cancelled |= cancelled;
+
+    info!("Returning {} transfers from {} associated token accounts", result.transfers.len(), accounts.len());
+    if let Some(progress) = progress {
+        progress(ProgressEvent::Done { transfers: result.transfers.len() });
+    }
+    Ok(result)
+}
+
+/// Owner of `token_account` according to the transaction's token balances,
+/// post balance first.
+fn token_account_owner(tx: &EncodedConfirmedTransactionWithStatusMeta, token_account: &str) -> Option<Pubkey> {
+    let meta = tx.transaction.meta.as_ref()?;
+    let index = account_keys(tx).iter().position(|key| *key == token_account)?;
+    let (OptionSerializer::Some(pre), OptionSerializer::Some(post)) =
+        (&meta.pre_token_balances, &meta.post_token_balances)
+    else {
+        return None;
+    };
+    post.iter()
+        .chain(pre)
+        .filter(|balance| usize::from(balance.account_index) == index)
+        .find_map(|balance| match &balance.owner {
+            OptionSerializer::Some(owner) => Pubkey::from_str(owner).ok(),
+            _ => None,
+        })
+}
+
+/// Counts the wallet's signatures dated within `range`
+/// without fetching any transactions, to size a scan before running it.
+/// Only `getSignaturesForAddress` (and `getBlockTime` for undated
+/// signatures) is called, at `confirmed` commitment with the default
+/// [`RetryPolicy`]. Signatures that can't be dated aren't counted. The count
+/// covers every transaction touching the wallet, not only token transfers.
+pub async fn count_in_range(
+    client: &(impl SolanaSource + Sync),
+    wallet: &str,
+    range: TimeRange,
+) -> Result<usize, IndexerError> {
+    count_signatures_in_range(
+        client,
+        wallet,
+        range.start(),
+        range.end(),
+        DEFAULT_SIGNATURE_PAGE_LIMIT,
+        &RetryPolicy::default(),
+        CommitmentConfig::confirmed(),
+    )
+    .await
+}
+
+/// [`count_in_range`] with explicit paging, retry and commitment settings.
+pub(crate) async fn count_signatures_in_range(
+    client: &(impl SolanaSource + Sync),
     wallet: &str,
-    usdc_mint: &str,
     start_time: DateTime<Utc>,
     end_time: DateTime<Utc>,
-) -> Result<Vec<Transfer>, Box<dyn std::error::Error>> {
-    let wallet_pubkey = Pubkey::from_str(wallet)?;
-    let usdc_mint_pubkey = Pubkey::from_str(usdc_mint)?;
+    page_limit: usize,
+    retry: &RetryPolicy,
+    commitment: CommitmentConfig,
+) -> Result<usize, IndexerError> {
+    let wallet_pubkey =
+        Pubkey::from_str(wallet).map_err(|_| IndexerError::InvalidPubkey(wallet.to_string()))?;
 
-    info!("Fetching signatures for wallet: {}", wallet);
-    let signatures = client
-        .get_signatures_for_address_with_config(
-            &wallet_pubkey,
-            solana_client::rpc_config::RpcSignaturesForAddressConfig {
-                before: None,
-                until: None,
-                limit: Some(5000),
-                commitment: Some(CommitmentConfig::confirmed()),
-                min_context_slot: None,
+    let before = seek_signature_cursor(client, end_time, retry, commitment).await;
+    let signatures =
+        fetch_signatures(client, &wallet_pubkey, start_time, before, None, page_limit, retry, commitment, None).await?;
+    let (in_range, _) = in_range_signatures(client, &signatures, start_time, end_time, retry).await?;
+
+    info!("Counted {} in-range signatures for wallet {}", in_range.len(), wallet);
+    Ok(in_range.len())
+}
+
+/// Indexes only the signatures newer than `until_signature`, for incremental
+/// runs that persist a checkpoint between invocations. Returns the indexing
+/// result along with the newest signature seen, which the caller should store
+/// as the next checkpoint (`None` if nothing new landed).
+///
+/// Fails with [`IndexerError::CheckpointUnavailable`] if the node no longer
+/// knows about `until_signature`, since it would otherwise page back through
+/// the wallet's entire retained history. Other parameters behave as they do
+/// for [`index_token_transfers`].
+#[allow(clippy::too_many_arguments)]
+pub async fn index_since_signature(
+    client: &(impl SolanaSource + Sync),
+    wallet: &str,
+    mints: &[Pubkey],
+    until_signature: &Signature,
+    page_limit: usize,
+    concurrency: usize,
+    batch_size: usize,
+    retry: RetryPolicy,
+    filter: &TransferFilter,
+    commitment: CommitmentConfig,
+) -> Result<(IndexResult, Option<Signature>), IndexerError> {
+    let client = &CountingSource::new(client);
+    let hits_before = client.cache_hits();
+    let wallet_pubkey =
+        Pubkey::from_str(wallet).map_err(|_| IndexerError::InvalidPubkey(wallet.to_string()))?;
+
+    let statuses = retry_with_backoff(&retry, || {
+        client.get_signature_statuses_with_history(std::slice::from_ref(until_signature))
+    })
+    .await?;
+    if statuses.value.first().is_none_or(|status| status.is_none()) {
+        warn!("Checkpoint signature {} is not available on this node", until_signature);
+        return Err(IndexerError::CheckpointUnavailable(until_signature.to_string()));
+    }
+
+    info!("Fetching signatures for wallet {} since {}", wallet, until_signature);
+    let signatures = fetch_signatures(
+        client,
+        &wallet_pubkey,
+        DateTime::<Utc>::MIN_UTC,
+        None,
+        Some(*until_signature),
+        page_limit,
+        &retry,
+        filter.listing_commitment(commitment),
+        None,
+    )
+    .await?;
+
+    info!("Found {} signatures", signatures.len());
+    let newest = signatures
+        .first()
+        .map(|sig_info| parse_signature(&sig_info.signature))
+        .transpose()?;
+    let (in_range, skipped_no_block_time) =
+        in_range_signatures(client, &signatures, DateTime::<Utc>::MIN_UTC, DateTime::<Utc>::MAX_UTC, &retry).await?;
+    filter.check_transaction_cap(in_range.len())?;
+    filter.check_block_times(skipped_no_block_time)?;
+
+    let mut result = fetch_and_process(
+        client,
+        in_range,
+        concurrency,
+        batch_size,
+        retry,
+        commitment,
+        None,
+        filter,
+        |tx, tx_time, signature| process_transaction(tx, &wallet_pubkey, mints, tx_time, signature, filter),
+    )
+    .await?;
+    result.skipped_no_block_time = skipped_no_block_time;
+    result.stats.signatures_fetched = signatures.len();
+    result.stats.rpc_calls = client.calls();
+    result.stats.cache_hits = client.cache_hits() - hits_before;
+
+    info!("Returning {} transfers ({} failed fetches)", result.transfers.len(), result.errors.len());
+    Ok((result, newest))
+}
+
+/// Indexes exactly the signatures between `before` and `until`, both
+/// exclusive, as the RPC's own cursors define them. `None` leaves that side
+/// open: no `before` starts at the newest signature and no `until` pages back
+/// through the node's retained history. No time filtering is applied, so the
+/// window doesn't move with block-time drift, which suits statement periods
+/// bounded by known signatures. Other parameters behave as they do for
+/// [`index_token_transfers`].
+#[allow(clippy::too_many_arguments)]
+pub async fn index_signature_window(
+    client: &(impl SolanaSource + Sync),
+    wallet: &str,
+    mints: &[Pubkey],
+    before: Option<Signature>,
+    until: Option<Signature>,
+    page_limit: usize,
+    concurrency: usize,
+    batch_size: usize,
+    retry: RetryPolicy,
+    filter: &TransferFilter,
+    commitment: CommitmentConfig,
+    progress: Option<&(dyn Fn(ProgressEvent) + Sync)>,
+) -> Result<IndexResult, IndexerError> {
+    let client = &CountingSource::new(client);
+    let hits_before = client.cache_hits();
+    let wallet_pubkey =
+        Pubkey::from_str(wallet).map_err(|_| IndexerError::InvalidPubkey(wallet.to_string()))?;
+
+    info!("Fetching signatures for wallet {} between {:?} and {:?}", wallet, before, until);
+    let signatures = fetch_signatures(
+        client,
+        &wallet_pubkey,
+        DateTime::<Utc>::MIN_UTC,
+        before,
+        until,
+        page_limit,
+        &retry,
+        filter.listing_commitment(commitment),
+        progress,
+    )
+    .await?;
+
+    info!("Found {} signatures", signatures.len());
+    let (in_range, skipped_no_block_time) =
+        in_range_signatures(client, &signatures, DateTime::<Utc>::MIN_UTC, DateTime::<Utc>::MAX_UTC, &retry).await?;
+    filter.check_transaction_cap(in_range.len())?;
+    filter.check_block_times(skipped_no_block_time)?;
+
+    let mut result = fetch_and_process(
+        client,
+        in_range,
+        concurrency,
+        batch_size,
+        retry,
+        commitment,
+        progress,
+        filter,
+        |tx, tx_time, signature| process_transaction(tx, &wallet_pubkey, mints, tx_time, signature, filter),
+    )
+    .await?;
+    result.skipped_no_block_time = skipped_no_block_time;
+    result.stats.signatures_fetched = signatures.len();
+    result.stats.rpc_calls = client.calls();
+    result.stats.cache_hits = client.cache_hits() - hits_before;
+
+    info!("Returning {} transfers ({} failed fetches)", result.transfers.len(), result.errors.len());
+    if let Some(progress) = progress {
+        progress(ProgressEvent::Done { transfers: result.transfers.len() });
+    }
+    Ok(result)
+}
+
+/// Extracts transfers from the given `signatures` without listing the
+/// wallet's signatures, for pipelines that get them from another source.
+/// Since the signatures come undated, every transaction is fetched and
+/// those whose block time falls outside `range` are dropped afterwards;
+/// duplicates are fetched once. Other parameters behave as they do for
+/// [`index_token_transfers`].
+#[allow(clippy::too_many_arguments)]
+pub async fn process_signatures(
+    client: &(impl SolanaSource + Sync),
+    wallet: &str,
+    mints: &[Pubkey],
+    signatures: &[Signature],
+    range: TimeRange,
+    concurrency: usize,
+    batch_size: usize,
+    retry: RetryPolicy,
+    filter: &TransferFilter,
+    commitment: CommitmentConfig,
+) -> Result<IndexResult, IndexerError> {
+    let client = &CountingSource::new(client);
+    let hits_before = client.cache_hits();
+    let wallet_pubkey =
+        Pubkey::from_str(wallet).map_err(|_| IndexerError::InvalidPubkey(wallet.to_string()))?;
+
+    let mut seen = HashSet::new();
+    // Dated by the range end until the body arrives, so recent ones stay eligible for the commitment fallback
+    let undated: Vec<(Signature, DateTime<Utc>)> = signatures
+        .iter()
+        .filter(|signature| seen.insert(**signature))
+        .map(|signature| (*signature, range.end()))
+        .collect();
+    filter.check_transaction_cap(undated.len())?;
+
+    info!("Processing {} supplied signatures for wallet {}", undated.len(), wallet);
+    let skipped_no_block_time = Cell::new(0);
+    let in_range = Cell::new(0);
+    let mut result = fetch_and_process(
+        client,
+        undated,
+        concurrency,
+        batch_size,
+        retry,
+        commitment,
+        None,
+        filter,
+        |tx, _, signature| {
+            let Some(tx_time) = tx.block_time.and_then(|t| Utc.timestamp_opt(t, 0).single()) else {
+                skipped_no_block_time.set(skipped_no_block_time.get() + 1);
+                filter.lenient(signature, "no block time")?;
+                return Ok(Vec::new());
+            };
+            if !range.contains(tx_time) {
+                trace!("Skipping signature {}: timestamp {} outside range", signature, tx_time);
+                return Ok(Vec::new());
+            }
+            in_range.set(in_range.get() + 1);
+            process_transaction(tx, &wallet_pubkey, mints, tx_time, signature, filter)
+        },
+    )
+    .await?;
+    result.skipped_no_block_time = skipped_no_block_time.get();
+    result.stats.signatures_fetched = signatures.len();
+    result.stats.in_range = in_range.get();
+    result.stats.rpc_calls = client.calls();
+    result.stats.cache_hits = client.cache_hits() - hits_before;
+
+    info!("Returning {} transfers ({} failed fetches)", result.transfers.len(), result.errors.len());
+    Ok(result)
+}
+
+/// Fetches transactions in batches of `batch_size`, up to `concurrency`
+/// batches at once, and extracts their transfers with `process`, returning
+/// them sorted along with any fetches that failed. Stops early, with
+/// `stats.cancelled` set, if `filter` is cancelled, and with
+/// `stats.truncated` set once `filter.max_transfers` is reached.
+#[allow(clippy::too_many_arguments)]
+pub(crate) async fn fetch_and_process<F>(
+    client: &(impl SolanaSource + Sync),
+    mut signatures: Vec<(Signature, DateTime<Utc>)>,
+    concurrency: usize,
+    batch_size: usize,
+    retry: RetryPolicy,
+    commitment: CommitmentConfig,
+    progress: Option<&(dyn Fn(ProgressEvent) + Sync)>,
+    filter: &TransferFilter,
+    process: F,
+) -> Result<IndexResult, IndexerError>
+where
+    F: Fn(&EncodedConfirmedTransactionWithStatusMeta, DateTime<Utc>, &Signature) -> Result<Vec<Transfer>, IndexerError>,
+{
+    let total = signatures.len();
+    // A transfer limit keeps the newest transactions, so those have to be fetched and
+    // processed in order; listings from several accounts arrive interleaved
+    if filter.max_transfers.is_some() {
+        signatures.sort_by_key(|(_, tx_time)| std::cmp::Reverse(*tx_time));
+    }
+    let batches: Vec<Vec<(Signature, DateTime<Utc>)>> =
+        signatures.chunks(batch_size.max(1)).map(<[_]>::to_vec).collect();
+    let fetches = stream::iter(batches)
+        .map(|batch| async move {
+            let fetched = fetch_batch(client, batch, &retry, commitment).await;
+            future::join_all(fetched.into_iter().map(|(signature, tx_time, result)| async move {
+                let (level, result) =
+                    with_commitment_fallback(client, &signature, tx_time, result, &retry, commitment, filter).await;
+                let result = with_encoding_fallback(client, &signature, result, &retry, level, filter).await;
+                (signature, tx_time, level, result)
+            }))
+            .await
+        });
+    let mut fetches = if filter.max_transfers.is_some() {
+        future::Either::Left(fetches.buffered(concurrency.max(1)).flat_map(stream::iter))
+    } else {
+        future::Either::Right(fetches.buffer_unordered(concurrency.max(1)).flat_map(stream::iter))
+    };
+
+    let mut result = IndexResult::default();
+    let mut index = 0;
+    let mut fetched_ok = 0;
+    loop {
+        // Dropping `fetches` on cancellation abandons the requests still in flight
+        let next = tokio::select! {
+            biased;
+            () = filter.cancelled() => {
+                info!("Cancelled with {} of {} transactions processed", index, total);
+                result.stats.cancelled = true;
+                break;
+            }
+            next = fetches.next() => next,
+        };
+        let Some((signature, tx_time, level, fetched)) = next else {
+            break;
+        };
+        index += 1;
+        match fetched {
+            Ok(tx) => {
+                fetched_ok += 1;
+                let mut transfers = process(&tx, tx_time, &signature)?;
+                for transfer in &mut transfers {
+                    transfer.commitment = Some(level);
+                }
+                result.transfers.extend(transfers);
+            }
+            Err(e) => {
+                warn!("Failed to get transaction {}: {}", signature, e);
+                result.errors.push((signature, e.into()));
+            }
+        }
+        if let Some(progress) = progress {
+            progress(ProgressEvent::TransactionProcessed { index, total });
+        }
+        if filter.max_transfers.is_some_and(|max| result.transfers.len() >= max) {
+            if index < total {
+                let found = result.transfers.len();
+                info!("Reached {} transfers with {} of {} transactions processed", found, index, total);
+                result.stats.truncated = true;
+            }
+            break;
+        }
+    }
+
+    result.stats.in_range = total;
+    result.stats.transactions_fetched = fetched_ok;
+    sort_transfers(&mut result.transfers);
+    Ok(result)
+}
+
+/// Indexes several wallets with a shared client, tagging each transfer with
+/// the wallet it was found for. Parameters after `wallets` behave as they do
+/// for [`index_token_transfers`].
+///
+/// A transfer between two tracked wallets is found once per side; only the
+/// sending side is kept, classified as [`TransferType::Internal`], so the
+/// movement isn't counted twice. The sides are paired on what arrived, so a
+/// Token-2022 fee transfer's gross send matches its net receipt. A wallet
+/// listed twice is indexed once.
+#[allow(clippy::too_many_arguments)]
+pub async fn index_token_transfers_multi(
+    client: &(impl SolanaSource + Sync),
+    wallets: &[&str],
+    mints: &[Pubkey],
+    range: TimeRange,
+    page_limit: usize,
+    concurrency: usize,
+    batch_size: usize,
+    retry: RetryPolicy,
+    filter: &TransferFilter,
+    commitment: CommitmentConfig,
+) -> Result<IndexResult, IndexerError> {
+    let mut transfers = Vec::new();
+    let mut errors = Vec::new();
+    let mut skipped_no_block_time = 0;
+    let mut stats = IndexStats::default();
+    let mut seen = HashSet::new();
+    for wallet in wallets.iter().filter(|wallet| seen.insert(**wallet)) {
+        let result = index_token_transfers(
+            client,
+            wallet,
+            mints,
+            range,
+            page_limit,
+            concurrency,
+            batch_size,
+            retry,
+            filter,
+            commitment,
+            None,
+        )
+        .await?;
+        transfers.extend(result.transfers);
+        errors.extend(result.errors);
+        skipped_no_block_time += result.skipped_no_block_time;
+        stats.add(&result.stats);
+    }
+
+    // Compared in base units, since a sent leg's net amount is computed by subtracting the fee
+    let arrived = |t: &Transfer| (t.net_amount * 10f64.powi(i32::from(t.decimals))).round() as u64;
+    let internal_sends: HashSet<(String, String, String, u64)> = transfers
+        .iter()
+        .filter(|t| t.transfer_type == TransferType::Sent)
+        .map(|t| (t.signature.clone(), t.wallet.clone(), t.mint.clone(), arrived(t)))
+        .collect();
+    transfers.retain(|t| {
+        let Some(counterparty) = &t.counterparty else {
+            return true;
+        };
+        t.transfer_type != TransferType::Received
+            || !internal_sends.contains(&(
+                t.signature.clone(),
+                counterparty.clone(),
+                t.mint.clone(),
+                arrived(t),
+            ))
+    });
+
+    let owned: HashSet<Pubkey> = wallets.iter().filter_map(|w| Pubkey::from_str(w).ok()).collect();
+    classify_internal(&mut transfers, &owned);
+
+    sort_transfers(&mut transfers);
+    Ok(IndexResult {
+        transfers,
+        errors,
+        skipped_no_block_time,
+        stats,
+    })
+}
+
+/// Reclassifies transfers whose counterparty is one of the `owned` wallets as
+/// [`TransferType::Internal`], so rebalancing between our own wallets isn't
+/// counted as income or expense.
+pub fn classify_internal(transfers: &mut [Transfer], owned: &HashSet<Pubkey>) {
+    for t in transfers.iter_mut() {
+        let is_owned = t
+            .counterparty
+            .as_deref()
+            .and_then(|counterparty| Pubkey::from_str(counterparty).ok())
+            .is_some_and(|counterparty| owned.contains(&counterparty));
+        if is_owned && t.transfer_type != TransferType::Unknown {
+            t.transfer_type = TransferType::Internal;
+        }
+    }
+}
+
+/// Returns the transfers in `current` that aren't in `previous`, in
+/// `current`'s order, for append-only syncs over overlapping runs. Transfers
+/// match on signature, token account, direction and raw amount; identical
+/// transfers are matched one for one, so a second identical leg in
+/// `current` still counts as new.
+pub fn diff(previous: &[Transfer], current: &[Transfer]) -> Vec<Transfer> {
+    let key = |t: &Transfer| (t.signature.clone(), t.token_account.clone(), t.transfer_type.clone(), t.amount_raw);
+    let mut known: HashMap<_, usize> = HashMap::new();
+    for t in previous {
+        *known.entry(key(t)).or_default() += 1;
+    }
+    current
+        .iter()
+        .filter(|t| match known.get_mut(&key(t)) {
+            Some(count) if *count > 0 => {
+                *count -= 1;
+                false
+            }
+            _ => true,
+        })
+        .cloned()
+        .collect()
+}
+
+/// Hashes the set of `transfers` into a value that is equal across runs
+/// exactly when the set is, so a scheduled job can skip re-exporting an
+/// unchanged result. Order and exact duplicates don't matter. Every field
+/// counts, so a transfer whose commitment or price changed makes a new
+/// fingerprint, and so does a release that adds a field to [`Transfer`].
+/// Uses 64-bit FNV-1a over each transfer's JSON form, which unlike
+/// [`std::hash::Hash`] is the same on every platform and toolchain.
+pub fn fingerprint(transfers: &[Transfer]) -> u64 {
+    let mut rows: Vec<String> = transfers
+        .iter()
+        .map(|t| serde_json::to_string(t).expect("transfers serialize to JSON"))
+        .collect();
+    rows.sort_unstable();
+    rows.dedup();
+
+    const FNV_OFFSET: u64 = 0xcbf2_9ce4_8422_2325;
+    const FNV_PRIME: u64 = 0x0000_0100_0000_01b3;
+    // Each row ends in a newline so adjacent rows can't run together
+    rows.iter()
+        .flat_map(|row| row.bytes().chain(std::iter::once(b'\n')))
+        .fold(FNV_OFFSET, |hash, byte| (hash ^ u64::from(byte)).wrapping_mul(FNV_PRIME))
+}
+
+/// Returns up to `limit` transfers starting at `offset`, for serving an
+/// already indexed result a page at a time. An offset past the end gives an
+/// empty page.
+pub fn page(transfers: &[Transfer], offset: usize, limit: usize) -> &[Transfer] {
+    let start = offset.min(transfers.len());
+    let end = start.saturating_add(limit).min(transfers.len());
+    &transfers[start..end]
+}
+
+/// Nets all of a wallet's Sent and Received legs for the same mint within a
+/// single transaction into one transfer carrying the net amount and overall
+/// direction. Routed transfers and CPIs that bounce funds through several of
+/// the wallet's accounts collapse to one row; if the legs cancel out (the
+/// wallet was only a passthrough) no transfer is kept. The merged transfer
+/// keeps the metadata of its largest leg. Internal and unknown transfers are
+/// left as is.
+pub fn merge_split_transfers(transfers: Vec<Transfer>) -> Vec<Transfer> {
+    let mut merged: Vec<(Transfer, i128, u64)> = Vec::new();
+    let mut passthrough = Vec::new();
+
+    for t in transfers {
+        let signed = match t.transfer_type {
+            TransferType::Received => i128::from(t.amount_raw),
+            TransferType::Sent => -i128::from(t.amount_raw),
+            TransferType::Internal | TransferType::Unknown => {
+                passthrough.push(t);
+                continue;
+            }
+        };
+
+        let existing = merged.iter_mut().find(|(m, _, _)| {
+            m.signature == t.signature && m.wallet == t.wallet && m.mint == t.mint
+        });
+        match existing {
+            Some((largest, net, largest_amount)) => {
+                *net += signed;
+                if t.amount_raw > *largest_amount {
+                    *largest_amount = t.amount_raw;
+                    *largest = t;
+                }
+            }
+            None => {
+                let amount = t.amount_raw;
+                merged.push((t, signed, amount));
+            }
+        }
+    }
+
+    let mut transfers: Vec<Transfer> = merged
+        .into_iter()
+        .filter(|(_, net, _)| *net != 0)
+        .map(|(mut t, net, _)| {
+            t.amount_raw = u64::try_from(net.unsigned_abs()).unwrap_or(u64::MAX);
+            t.amount = t.amount_raw as f64 / 10f64.powi(i32::from(t.decimals));
+            t.transfer_type = if net > 0 {
+                TransferType::Received
+            } else {
+                TransferType::Sent
+            };
+            t
+        })
+        .chain(passthrough)
+        .collect();
+    sort_transfers(&mut transfers);
+    transfers
+}
+
+/// Sorts ascending by date, then signature. The sort is stable, so the
+/// transfers of one transaction keep the order they were extracted in and
+/// repeated runs over the same history produce identical output.
+pub(crate) fn sort_transfers(transfers: &mut [Transfer]) {
+    transfers.sort_by(|a, b| a.date.cmp(&b.date).then_with(|| a.signature.cmp(&b.signature)));
+}
+
+/// Streams transfers for every mint in `mints` as they are discovered,
+/// without collecting the wallet's history in memory first.
+///
+/// Signature pages and transaction bodies are fetched lazily as the consumer
+/// polls, one at a time, so memory use stays constant regardless of history
+/// length. Transfers are yielded newest signature first. The stream ends
+/// early once [`TransferFilter::cancellation`] is cancelled. The remaining
+/// parameters behave as they do for [`index_token_transfers`].
+#[allow(clippy::too_many_arguments)]
+pub fn index_token_transfers_stream<'a>(
+    client: &'a (impl SolanaSource + Sync),
+    wallet: &str,
+    mints: &'a [Pubkey],
+    range: TimeRange,
+    page_limit: usize,
+    retry: RetryPolicy,
+    filter: &'a TransferFilter,
+    commitment: CommitmentConfig,
+) -> impl Stream<Item = Result<Transfer, IndexerError>> + 'a {
+    let wallet_pubkey =
+        Pubkey::from_str(wallet).map_err(|_| IndexerError::InvalidPubkey(wallet.to_string()));
+
+    stream::once(future::ready(wallet_pubkey))
+        .map_ok(move |wallet_pubkey| {
+            // `None` once the last page has been fetched, otherwise the cursor for the next page
+            let pages = stream::try_unfold(Some(None), move |cursor| async move {
+                let Some(before) = cursor else {
+                    return Ok::<_, IndexerError>(None);
+                };
+                let (page, next, _) = fetch_signature_page(
+                    client,
+                    &wallet_pubkey,
+                    before,
+                    None,
+                    range.start(),
+                    page_limit,
+                    &retry,
+                    filter.listing_commitment(commitment),
+                )
+                .await?;
+                Ok(Some((stream::iter(page.into_iter().map(Ok)), next.map(Some))))
+            });
+
+            pages
+                .try_flatten()
+                .try_filter_map(move |sig_info| async move {
+                    let block_time = match sig_info.block_time {
+                        Some(block_time) => Some(block_time),
+                        None => fetch_block_time(client, sig_info.slot, &retry).await,
+                    };
+                    if block_time.is_none() {
+                        filter.check_block_times(1)?;
+                    }
+                    in_range_signature(&sig_info, block_time, range.start(), range.end())
+                })
+                .and_then(move |(signature, tx_time)| async move {
+                    let fetched = fetch_transaction(client, &signature, &retry, commitment).await;
+                    let (level, fetched) =
+                        with_commitment_fallback(client, &signature, tx_time, fetched, &retry, commitment, filter)
+                            .await;
+                    let fetched = with_encoding_fallback(client, &signature, fetched, &retry, level, filter).await;
+                    let mut transfers = process_transaction(&fetched?, &wallet_pubkey, mints, tx_time, &signature, filter)?;
+                    for transfer in &mut transfers {
+                        transfer.commitment = Some(level);
+                    }
+                    Ok(stream::iter(transfers.into_iter().map(Ok)))
+                })
+                .try_flatten()
+        })
+        .try_flatten()
+        .take_until(filter.cancelled())
+}
+
+/// Pages backwards through the wallet's signatures (newest first) using the
+/// `before` cursor until a short page is returned, a signature older than
+/// `start_time` is reached, or the node stops at `until`. Paging starts at
+/// `before` if given, otherwise at the newest signature.
+#[allow(clippy::too_many_arguments)]
+pub(crate) async fn fetch_signatures(
+    client: &(impl SolanaSource + Sync),
+    wallet_pubkey: &Pubkey,
+    start_time: DateTime<Utc>,
+    before: Option<Signature>,
+    until: Option<Signature>,
+    page_limit: usize,
+    retry: &RetryPolicy,
+    commitment: CommitmentConfig,
+    progress: Option<&(dyn Fn(ProgressEvent) + Sync)>,
+) -> Result<Vec<RpcConfirmedTransactionStatusWithSignature>, IndexerError> {
+    let mut signatures = Vec::new();
+    let mut seen = HashSet::new();
+    let mut before = before;
+
+    loop {
+        let (page, next, _) =
+            fetch_signature_page(client, wallet_pubkey, before, until, start_time, page_limit, retry, commitment)
+                .await?;
+        signatures.extend(page.into_iter().filter(|sig_info| seen.insert(sig_info.signature.clone())));
+        if let Some(progress) = progress {
+            progress(ProgressEvent::SignaturesFetched(signatures.len()));
+        }
+
+        match next {
+            Some(cursor) => before = Some(cursor),
+            None => return Ok(signatures),
+        }
+    }
+}
+
+/// Signatures listed by [`fetch_signature_pages`].
+pub(crate) struct SignatureListing {
+    pub(crate) signatures: Vec<RpcConfirmedTransactionStatusWithSignature>,
+    /// Paging stopped at `max_pages` with pages left.
+    pub(crate) truncated: bool,
+    /// Block time of the oldest signature, when it is newer than the start
+    /// of the range and paging ended on an empty page right after a full
+    /// one. This is how a node that pruned older history looks, though an
+    /// address whose history happens to end on a page boundary looks the
+    /// same.
+    pub(crate) oldest_available: Option<DateTime<Utc>>,
+    /// Paging stopped early because the run was cancelled.
+    pub(crate) cancelled: bool,
+}
+
+/// Like [`fetch_signatures`] without an `until` bound, but stops after
+/// `max_pages` pages if given, and checks whether history older than the
+/// oldest signature may have been pruned by the node.
+#[allow(clippy::too_many_arguments)]
+pub(crate) async fn fetch_signature_pages(
+    client: &(impl SolanaSource + Sync),
+    wallet_pubkey: &Pubkey,
+    start_time: DateTime<Utc>,
+    before: Option<Signature>,
+    page_limit: usize,
+    filter: &TransferFilter,
+    retry: &RetryPolicy,
+    commitment: CommitmentConfig,
+    progress: Option<&(dyn Fn(ProgressEvent) + Sync)>,
+) -> Result<SignatureListing, IndexerError> {
+    let mut signatures = Vec::new();
+    let mut seen = HashSet::new();
+    let mut before = before;
+    let mut pages = 0;
+
+    loop {
+        if filter.max_pages.is_some_and(|max_pages| pages >= max_pages) {
+            info!("Stopped after {} signature pages; older signatures were not fetched", pages);
+            return Ok(SignatureListing { signatures, truncated: true, oldest_available: None, cancelled: false });
+        }
+        if filter.is_cancelled() {
+            info!("Cancelled after {} signature pages", pages);
+            return Ok(SignatureListing { signatures, truncated: false, oldest_available: None, cancelled: true });
+        }
+        let (page, next, exhausted) =
+            fetch_signature_page(
+                client,
+                wallet_pubkey,
+                before,
+                None,
+                start_time,
+                page_limit,
+                retry,
+                filter.listing_commitment(commitment),
+            )
+            .await?;
+        pages += 1;
+        signatures.extend(page.into_iter().filter(|sig_info| seen.insert(sig_info.signature.clone())));
+        if let Some(progress) = progress {
+            progress(ProgressEvent::SignaturesFetched(signatures.len()));
+        }
+
+        match next {
+            Some(cursor) => before = Some(cursor),
+            None => {
+                let oldest_available = if exhausted && pages > 1 {
+                    signatures
+                        .iter()
+                        .rev()
+                        .find_map(|sig_info| sig_info.block_time)
+                        .filter(|oldest| *oldest > start_time.timestamp())
+                        .and_then(|oldest| Utc.timestamp_opt(oldest, 0).single())
+                } else {
+                    None
+                };
+                return Ok(SignatureListing { signatures, truncated: false, oldest_available, cancelled: false });
+            }
+        }
+    }
+}
+
+/// Fetches one page of signatures older than `before` (and newer than
+/// `until`), truncated at the first signature older than `start_time`. Also
+/// returns the cursor for the next page, or `None` when there is nothing
+/// further to fetch, and whether the node returned no signatures at all
+/// beyond the cursors.
+#[allow(clippy::too_many_arguments)]
+pub(crate) async fn fetch_signature_page(
+    client: &(impl SolanaSource + Sync),
+    wallet_pubkey: &Pubkey,
+    before: Option<Signature>,
+    until: Option<Signature>,
+    start_time: DateTime<Utc>,
+    page_limit: usize,
+    retry: &RetryPolicy,
+    commitment: CommitmentConfig,
+) -> Result<(Vec<RpcConfirmedTransactionStatusWithSignature>, Option<Signature>, bool), IndexerError> {
+    let page_limit = page_limit.clamp(1, DEFAULT_SIGNATURE_PAGE_LIMIT);
+    let mut page = retry_with_backoff(retry, || {
+        client.get_signatures_for_address_with_config(
+            wallet_pubkey,
+            GetConfirmedSignaturesForAddress2Config {
+                before,
+                until,
+                limit: Some(page_limit),
+                commitment: Some(commitment),
             },
         )
-        .await
+    })
+    .await
+    .map_err(|e| {
+        error!("Failed to get signatures: {}", e);
+        IndexerError::from(e)
+    })?;
+
+    let page_len = page.len();
+    debug!("Fetched page of {} signatures", page_len);
+    let last = page.last().map(|last| parse_signature(&last.signature)).transpose()?;
+
+    // Some providers repeat the cursor signatures at the page edges, which
+    // would otherwise be indexed twice
+    let boundaries: Vec<String> = before.iter().chain(until.iter()).map(|sig| sig.to_string()).collect();
+    page.retain(|sig_info| !boundaries.contains(&sig_info.signature));
+    let exhausted = page.is_empty();
+
+    if let Some(cutoff) = page
+        .iter()
+        .position(|sig_info| sig_info.block_time.is_some_and(|t| t < start_time.timestamp()))
+    {
+        debug!("Reached signature {} older than {}, stopping", page[cutoff].signature, start_time);
+        page.truncate(cutoff);
+        return Ok((page, None, false));
+    }
+
+    // A page holding nothing but the cursor itself would loop forever
+    let next = last.filter(|last| page_len >= page_limit && Some(*last) != before);
+    Ok((page, next, exhausted))
+}
+
+/// Keeps the signatures with a block time inside `[start_time, end_time]`,
+/// looking up the block time of any signature listed without one. Also
+/// returns how many signatures were skipped because no block time could be
+/// found.
+pub(crate) async fn in_range_signatures(
+    client: &(impl SolanaSource + Sync),
+    signatures: &[RpcConfirmedTransactionStatusWithSignature],
+    start_time: DateTime<Utc>,
+    end_time: DateTime<Utc>,
+    retry: &RetryPolicy,
+) -> Result<(Vec<(Signature, DateTime<Utc>)>, usize), IndexerError> {
+    let mut in_range = Vec::new();
+    let mut skipped_no_block_time = 0;
+    // Undated signatures tend to come from the same few blocks
+    let mut block_times: HashMap<Slot, Option<i64>> = HashMap::new();
+    for sig_info in signatures {
+        let block_time = match sig_info.block_time {
+            Some(block_time) => Some(block_time),
+            None => match block_times.get(&sig_info.slot) {
+                Some(block_time) => *block_time,
+                None => {
+                    let block_time = fetch_block_time(client, sig_info.slot, retry).await;
+                    block_times.insert(sig_info.slot, block_time);
+                    block_time
+                }
+            },
+        };
+        if block_time.is_none() {
+            skipped_no_block_time += 1;
+        }
+        if let Some(entry) = in_range_signature(sig_info, block_time, start_time, end_time)? {
+            in_range.push(entry);
+        }
+    }
+    Ok((in_range, skipped_no_block_time))
+}
+
+/// Looks up the block time for a slot when the signature listing didn't
+/// include one, returning `None` if the node can't provide it either.
+async fn fetch_block_time(client: &(impl SolanaSource + Sync), slot: Slot, retry: &RetryPolicy) -> Option<i64> {
+    match retry_with_backoff(retry, || client.get_block_time(slot)).await {
+        Ok(block_time) => Some(block_time),
+        Err(e) => {
+            warn!("No block time available for slot {}: {}", slot, e);
+            None
+        }
+    }
+}
+
+/// Parses a fetched signature and checks its `block_time`, returning `None`
+/// for signatures outside `[start_time, end_time]` or without a block time.
+fn in_range_signature(
+    sig_info: &RpcConfirmedTransactionStatusWithSignature,
+    block_time: Option<i64>,
+    start_time: DateTime<Utc>,
+    end_time: DateTime<Utc>,
+) -> Result<Option<(Signature, DateTime<Utc>)>, IndexerError> {
+    let signature = parse_signature(&sig_info.signature)?;
+    let block_time = block_time
+        .map(|t| Utc.timestamp_opt(t, 0).single().ok_or(IndexerError::InvalidTimestamp(t)))
+        .transpose()
         .map_err(|e| {
-            error!("Failed to get signatures: {}", e);
-            Box::new(e) as Box<dyn std::error::Error>
+            error!("Invalid block time for signature {}: {}", signature, e);
+            e
         })?;
 
-    info!("Found {} signatures", signatures.len());
-    let mut transfers = Vec::new();
+    match block_time {
+        Some(tx_time) if tx_time < start_time || tx_time > end_time => {
+            trace!(
+                "Skipping signature {}: timestamp {} outside range [{}, {}]",
+                signature, tx_time, start_time, end_time
+            );
+            Ok(None)
+        }
+        Some(tx_time) => Ok(Some((signature, tx_time))),
+        None => {
+            debug!("No block time for signature: {}", signature);
+            Ok(None)
+        }
+    }
+}
 
-    for sig_info in signatures {
-        let signature = Signature::from_str(&sig_info.signature)?;
-        let block_time = sig_info
-            .block_time
-            .map(|t| Utc.timestamp_opt(t, 0).single().ok_or("Invalid timestamp"))
-            .transpose()
-            .map_err(|e| {
-                error!("Invalid block time for signature {}: {}", signature, e);
-                Box::new(std::io::Error::new(std::io::ErrorKind::Other, e))
-            })?;
-
-        if let Some(tx_time) = block_time {
-            if tx_time < start_time || tx_time > end_time {
-                info!(
-                    "Skipping signature {}: timestamp {} outside range [{}, {}]",
-                    signature, tx_time, start_time, end_time
-                );
-                continue;
+/// Fetches a batch of transactions in one request, falling back to fetching
+/// individually (with retries) any that the batch didn't return. A batch of
+/// one goes straight to the individual fetch.
+async fn fetch_batch(
+    client: &(impl SolanaSource + Sync),
+    batch: Vec<(Signature, DateTime<Utc>)>,
+    retry: &RetryPolicy,
+    commitment: CommitmentConfig,
+) -> Vec<(Signature, DateTime<Utc>, Result<EncodedConfirmedTransactionWithStatusMeta, ClientError>)> {
+    let batched = if batch.len() > 1 {
+        debug!("Fetching batch of {} transactions", batch.len());
+        let signatures: Vec<Signature> = batch.iter().map(|(signature, _)| *signature).collect();
+        client
+            .get_transactions_with_config(&signatures, transaction_config(commitment))
+            .await
+    } else {
+        Vec::new()
+    };
+
+    let mut batched = batched.into_iter();
+    let mut results = Vec::with_capacity(batch.len());
+    for (signature, tx_time) in batch {
+        let result = match batched.next() {
+            Some(Ok(tx)) => Ok(tx),
+            Some(Err(e)) => {
+                warn!("Batch fetch of {} failed, retrying individually: {}", signature, e);
+                fetch_transaction(client, &signature, retry, commitment).await
             }
+            None => fetch_transaction(client, &signature, retry, commitment).await,
+        };
+        results.push((signature, tx_time, result));
+    }
+    results
+}
 
-            info!("Fetching transaction for signature: {}", signature);
-            let tx = client
-                .get_transaction(&signature, UiTransactionEncoding::JsonParsed)
-                .await
-                .map_err(|e| {
-                    error!("Failed to get transaction {}: {}", signature, e);
-                    Box::new(e) as Box<dyn std::error::Error>
-                })?;
+pub(crate) async fn fetch_transaction(
+    client: &(impl SolanaSource + Sync),
+    signature: &Signature,
+    retry: &RetryPolicy,
+    commitment: CommitmentConfig,
+) -> Result<EncodedConfirmedTransactionWithStatusMeta, ClientError> {
+    trace!("Fetching transaction for signature: {}", signature);
+    retry_with_backoff(retry, || client.get_transaction_with_config(signature, transaction_config(commitment))).await
+}
 
-            transfers.extend(process_transaction(&tx, &wallet_pubkey, &usdc_mint_pubkey, tx_time, &signature));
-        } else {
-            warn!("No block time for signature: {}", signature);
+/// Refetches a transaction the node couldn't find at `commitment` at the
+/// filter's fallback commitment, if it is recent enough. Returns the
+/// commitment the result was fetched at.
+async fn with_commitment_fallback(
+    client: &(impl SolanaSource + Sync),
+    signature: &Signature,
+    tx_time: DateTime<Utc>,
+    fetched: Result<EncodedConfirmedTransactionWithStatusMeta, ClientError>,
+    retry: &RetryPolicy,
+    commitment: CommitmentConfig,
+    filter: &TransferFilter,
+) -> (CommitmentLevel, Result<EncodedConfirmedTransactionWithStatusMeta, ClientError>) {
+    match (fetched, filter.commitment_fallback) {
+        (Err(e), Some(fallback))
+            if fallback.commitment != commitment && tx_time > filter.now() - fallback.max_age && is_unavailable(&e) =>
+        {
+            debug!(%signature, commitment = ?fallback.commitment.commitment, "Transaction not found, retrying at fallback");
+            (fallback.commitment.commitment, fetch_transaction(client, signature, retry, fallback.commitment).await)
         }
+        (fetched, _) => (commitment.commitment, fetched),
     }
+}
 
-    info!("Returning {} transfers", transfers.len());
-    Ok(transfers)
+/// Refetches a transaction whose `JsonParsed` body came back without token
+/// balances as plain `Json`, for nodes that still serve those with the raw
+/// encoding, when the filter asks for it. Keeps the parsed body if the
+/// refetch fails or has no balances either.
+async fn with_encoding_fallback(
+    client: &(impl SolanaSource + Sync),
+    signature: &Signature,
+    fetched: Result<EncodedConfirmedTransactionWithStatusMeta, ClientError>,
+    retry: &RetryPolicy,
+    commitment: CommitmentLevel,
+    filter: &TransferFilter,
+) -> Result<EncodedConfirmedTransactionWithStatusMeta, ClientError> {
+    let tx = match fetched {
+        Ok(tx) if filter.encoding_fallback && !has_token_balances(&tx) => tx,
+        fetched => {
+            if fetched.is_ok() {
+                debug!(%signature, encoding = "jsonParsed", "Fetched transaction");
+            }
+            return fetched;
+        }
+    };
+    let config = RpcTransactionConfig {
+        encoding: Some(UiTransactionEncoding::Json),
+        ..transaction_config(CommitmentConfig { commitment })
+    };
+    match retry_with_backoff(retry, || client.get_transaction_with_config(signature, config)).await {
+        Ok(raw) if has_token_balances(&raw) => {
+            debug!(%signature, encoding = "json", "Fetched transaction after no token balances in jsonParsed");
+            Ok(raw)
+        }
+        Ok(_) => {
+            debug!(%signature, encoding = "jsonParsed", "No token balances in either encoding");
+            Ok(tx)
+        }
+        Err(e) => {
+            debug!(%signature, encoding = "jsonParsed", "Json refetch failed: {}", e);
+            Ok(tx)
+        }
+    }
+}
+
+fn has_token_balances(tx: &EncodedConfirmedTransactionWithStatusMeta) -> bool {
+    tx.transaction.meta.as_ref().is_some_and(|meta| {
+        matches!(meta.pre_token_balances, OptionSerializer::Some(_))
+            && matches!(meta.post_token_balances, OptionSerializer::Some(_))
+    })
+}
+
+fn transaction_config(commitment: CommitmentConfig) -> RpcTransactionConfig {
+    RpcTransactionConfig {
+        encoding: Some(UiTransactionEncoding::JsonParsed),
+        commitment: Some(commitment),
+        max_supported_transaction_version: Some(0),
+    }
+}
+
+pub(crate) fn parse_signature(signature: &str) -> Result<Signature, IndexerError> {
+    Signature::from_str(signature).map_err(|_| IndexerError::SignatureParse(signature.to_string()))
 }
 
-fn process_transaction(
+pub(crate) fn process_transaction(
     tx: &EncodedConfirmedTransactionWithStatusMeta,
     wallet_pubkey: &Pubkey,
-    usdc_mint_pubkey: &Pubkey,
+    mints: &[Pubkey],
     tx_time: DateTime<Utc>,
     signature: &Signature,
-) -> Vec<Transfer> {
+    filter: &TransferFilter,
+) -> Result<Vec<Transfer>, IndexerError> {
+    let _span = debug_span!("process_transaction", wallet = %wallet_pubkey, %signature, slot = tx.slot).entered();
     let mut transfers = Vec::new();
+    let tracked_mints: Vec<String> = mints.iter().map(|mint| mint.to_string()).collect();
+    let memo = parse_memo(tx);
 
     if let Some(meta) = &tx.transaction.meta {
-        let pre_balances = meta.pre_token_balances.as_ref().unwrap_or(&vec![]);
-        let post_balances = meta.post_token_balances.as_ref().unwrap_or(&vec![]);
+        let success = meta.err.is_none();
+        if !success && !filter.include_failed {
+            debug!(%signature, err = ?meta.err, "Skipping failed transaction");
+            return Ok(transfers);
+        }
+
+        // Old or pruned transactions may have no token balances recorded at all, which is
+        // different from balances that were recorded but didn't change
+        let (OptionSerializer::Some(pre_balances), OptionSerializer::Some(post_balances)) =
+            (&meta.pre_token_balances, &meta.post_token_balances)
+        else {
+            debug!(%signature, "No token balances recorded for transaction");
+            return filter.lenient(signature, "no token balances recorded").map(|()| transfers);
+        };
+        let post_balances = &with_closed_accounts(pre_balances, post_balances);
+
+        if filter.parse_mode == ParseMode::Instructions {
+            let mut transfers = instruction_transfers(
+                tx,
+                wallet_pubkey,
+                &tracked_mints,
+                tx_time,
+                signature,
+                filter,
+                pre_balances,
+                post_balances,
+            );
+            mark_swaps(&mut transfers, pre_balances, post_balances, wallet_pubkey);
+            transfers.retain(|t| !filter.excludes_counterparty(t));
+            order_transfers(&mut transfers);
+            label_assets(&mut transfers, filter);
+            return Ok(transfers);
+        }
+
+        // Some responses repeat a balance entry; keep one transfer per account and direction
+        let mut seen = HashSet::new();
+        let paid_fee = is_fee_payer(tx, wallet_pubkey);
+        let mut post_balances_by_index: Vec<&UiTransactionTokenBalance> = post_balances.iter().collect();
+        post_balances_by_index.sort_by_key(|post| post.account_index);
+
+        for post in post_balances_by_index {
+            // Only the mint is compared, not the token program, so SPL Token and Token-2022
+            // balances are handled alike
+            if tracked_mints.contains(&post.mint) {
+                // Accounts created in this transaction have no pre balance for the mint
+                let pre = pre_balance(pre_balances, post);
 
-        for (pre, post) in pre_balances.iter().zip(post_balances.iter()) {
-            // Check if token mint and owner match
-            if pre.mint == *usdc_mint_pubkey && post.mint == *usdc_mint_pubkey {
                 // Check that the wallet is one of the owners (pre or post) -- mostly pre.owner and post.owner are same
-                if let (Some(pre_owner), Some(post_owner)) = (&pre.owner, &post.owner) {
-                    if pre_owner != wallet_pubkey.to_string() && post_owner != wallet_pubkey.to_string() {
-                        continue; // Not related to wallet, skip
+                let wallet = wallet_pubkey.to_string();
+                let pre_owner = pre.and_then(|pre| match &pre.owner {
+                    OptionSerializer::Some(owner) => Some(owner),
+                    _ => None,
+                });
+                let post_owner = match &post.owner {
+                    OptionSerializer::Some(owner) => Some(owner),
+                    _ => None,
+                };
+                let mut unknown_owner = false;
+                let owned = match (post_owner, pre_owner) {
+                    // Balances recorded before the owner field existed
+                    (None, None) => {
+                        let owned = is_wallet_ata(tx, post, wallet_pubkey);
+                        if !owned && filter.include_unknown {
+                            unknown_owner = true;
+                        } else if !owned {
+                            filter.lenient(signature, "token balance without an owner")?;
+                        }
+                        owned
                     }
-                } else {
-                    // Skip if no owner info
-                    continue;
+                    (post_owner, pre_owner) => post_owner == Some(&wallet) || pre_owner == Some(&wallet),
+                };
+                if !owned && !unknown_owner {
+                    continue; // Not related to wallet, skip
                 }
 
                 // Calculate amount change
-                let pre_amount = pre.ui_token_amount.ui_amount.unwrap_or(0.0);
-                let post_amount = post.ui_token_amount.ui_amount.unwrap_or(0.0);
-                let diff = post_amount - pre_amount;
+                // Calculated on the integer base units, since ui_amount is a lossy float. The
+                // comparison is exact, so float noise can't register as a change and no
+                // epsilon is needed
+                let pre_amount = pre.map(raw_amount).unwrap_or(0);
+                let diff = i128::from(raw_amount(post)) - i128::from(pre_amount);
 
-                if diff.abs() < f64::EPSILON {
+                if diff == 0 && !filter.include_zero_amount {
                     continue; // No transfer amount change
                 }
 
-                let transfer_type = if diff > 0.0 {
+                let decimals = post.ui_token_amount.decimals;
+                let amount_raw = u64::try_from(diff.unsigned_abs()).unwrap_or(u64::MAX);
+                let amount = amount_raw as f64 / 10f64.powi(i32::from(decimals));
+
+                if filter.excludes_amount(&post.mint, amount) {
+                    continue; // Dust below the mint's threshold, or outside the amount range
+                }
+
+                // An untouched balance has no direction; it's reported as received
+                let transfer_type = if unknown_owner {
+                    TransferType::Unknown
+                } else if diff >= 0 {
                     TransferType::Received
                 } else {
                     TransferType::Sent
                 };
 
-                let from = if transfer_type == TransferType::Sent {
-                    Some(wallet_pubkey.to_string())
+                if filter.excludes_direction(&transfer_type) {
+                    continue;
+                }
+
+                if !seen.insert((post.account_index, transfer_type.clone())) {
+                    continue;
+                }
+
+                let counterparty_balance = find_counterparty(pre_balances, post_balances, &post.mint, diff, &wallet);
+                let counterparty = counterparty_balance.and_then(|balance| match &balance.owner {
+                    OptionSerializer::Some(owner) => Some(owner.clone()),
+                    _ => None,
+                });
+
+                // The receiving token account was created in this transaction if it has no pre
+                // balance. An account closed and reopened in the same transaction keeps its
+                // pre balance, so it isn't flagged
+                let receiving_balance = if transfer_type == TransferType::Received {
+                    Some(post)
                 } else {
-                    None
+                    counterparty_balance
                 };
+                let account_initialized =
+                    receiving_balance.is_some_and(|receiving| pre_balance(pre_balances, receiving).is_none());
 
-                let to = if transfer_type == TransferType::Received {
-                    Some(wallet_pubkey.to_string())
-                } else {
-                    None
+                let token_account = account_key(tx, post.account_index);
+                let counterparty_token_account = counterparty_balance
+                    .and_then(|balance| account_key(tx, balance.account_index))
+                    .map(str::to_string);
+
+                // Wrapping creates/syncs and unwrapping closes our own WSOL account with no
+                // WSOL counterparty; that's SOL changing form, not a transfer
+                if post.mint == WSOL_MINT
+                    && !filter.track_wsol_wrapping
+                    && counterparty.is_none()
+                    && token_account
+                        .is_some_and(|account| has_account_instruction(tx, account, &WSOL_WRAP_INSTRUCTIONS))
+                {
+                    debug!(%signature, "Skipping WSOL wrap/unwrap");
+                    continue;
+                }
+
+                // Token-2022 mints with a transfer fee credit the recipient less than was debited
+                let fee = token_account.and_then(|account| withheld_fee(tx, account));
+                let (gross_amount, net_amount) = match fee {
+                    Some(fee) if diff > 0 => (amount + fee, amount),
+                    Some(fee) if diff < 0 => (amount, amount - fee),
+                    _ => (amount, amount),
                 };
+                let change = token_account.and_then(|account| balance_change(tx, account));
+                let caller = change.and_then(|(_, caller)| caller);
 
                 transfers.push(Transfer {
-                    signature: signature.to_string(),
-                    timestamp: tx_time,
-                    from,
-                    to,
-                    amount: diff.abs(),
+                    date: tx_time,
+                    amount,
+                    amount_raw,
+                    decimals,
                     transfer_type,
+                    signature: signature.to_string(),
+                    mint: post.mint.clone(),
+                    success,
+                    counterparty,
+                    counterparty_token_account,
+                    memo: memo.clone(),
+                    wallet,
+                    token_account: token_account.unwrap_or_default().to_string(),
+                    fee,
+                    gross_amount,
+                    net_amount,
+                    usd_value: None,
+                    slot: tx.slot,
+                    block_hash: None,
+                    swap_group: None,
+                    asset: None,
+                    kind: change.map(|(kind, _)| kind),
+                    commitment: None,
+                    via_cpi: caller.is_some(),
+                    program_id: caller.map(str::to_string),
+                    fee_lamports: meta.fee,
+                    paid_fee,
+                    account_initialized,
                 });
             }
         }
+
+        if transfers.is_empty() {
+            debug!(%signature, "Token balances recorded but they produced no transfers");
+        }
+        mark_swaps(&mut transfers, pre_balances, post_balances, wallet_pubkey);
+        transfers.retain(|t| !filter.excludes_counterparty(t));
+        order_transfers(&mut transfers);
+        label_assets(&mut transfers, filter);
+    } else {
+        filter.lenient(signature, "no status meta")?;
+    }
+
+    Ok(transfers)
+}
+
+/// Puts one transaction's transfers in a stable order: sent, internal,
+/// received, then unknown. The sort is stable, so each direction keeps the account
+/// index (or instruction) order it was built in.
+fn order_transfers(transfers: &mut [Transfer]) {
+    transfers.sort_by_key(|t| match t.transfer_type {
+        TransferType::Sent => 0,
+        TransferType::Internal => 1,
+        TransferType::Received => 2,
+        TransferType::Unknown => 3,
+    });
+}
+
+fn label_assets(transfers: &mut [Transfer], filter: &TransferFilter) {
+    if filter.asset_labels.is_empty() {
+        return;
+    }
+    for t in transfers.iter_mut() {
+        t.asset = Pubkey::from_str(&t.mint)
+            .ok()
+            .and_then(|mint| filter.asset_labels.get(&mint))
+            .cloned();
+    }
+}
+
+/// Marks the transfers of a swap: a transaction in which the wallet's
+/// accounts lose one mint and gain another, tracked or not. Each sent leg of
+/// a decreasing mint and received leg of an increasing one gets the
+/// transaction signature as its `swap_group`, so reports can set trading
+/// apart from payments.
+fn mark_swaps(
+    transfers: &mut [Transfer],
+    pre_balances: &[UiTransactionTokenBalance],
+    post_balances: &[UiTransactionTokenBalance],
+    wallet_pubkey: &Pubkey,
+) {
+    let wallet = wallet_pubkey.to_string();
+    let mut net_by_mint: HashMap<&str, i128> = HashMap::new();
+    for post in post_balances {
+        let owned = matches!(&post.owner, OptionSerializer::Some(owner) if *owner == wallet);
+        if !owned {
+            continue;
+        }
+        let pre_amount = pre_balance(pre_balances, post).map(raw_amount).unwrap_or(0);
+        *net_by_mint.entry(post.mint.as_str()).or_default() +=
+            i128::from(raw_amount(post)) - i128::from(pre_amount);
+    }
+
+    let decreased: Vec<&str> = net_by_mint.iter().filter(|(_, net)| **net < 0).map(|(mint, _)| *mint).collect();
+    let increased: Vec<&str> = net_by_mint.iter().filter(|(_, net)| **net > 0).map(|(mint, _)| *mint).collect();
+    for t in transfers.iter_mut() {
+        let (own_side, other_side) = match t.transfer_type {
+            TransferType::Sent => (&decreased, &increased),
+            TransferType::Received => (&increased, &decreased),
+            TransferType::Internal | TransferType::Unknown => continue,
+        };
+        if own_side.contains(&t.mint.as_str()) && other_side.iter().any(|mint| *mint != t.mint) {
+            t.swap_group = Some(t.signature.clone());
+        }
+    }
+}
+
+/// Extracts one transfer per token transfer instruction moving a tracked
+/// mint into or out of an account owned by `wallet_pubkey`. The token
+/// balances supply what the instructions leave out: account owners, and the
+/// mint and decimals of plain `transfer`s. A transfer between two of the
+/// wallet's own accounts is reported once, as [`TransferType::Internal`].
+#[allow(clippy::too_many_arguments)]
+fn instruction_transfers(
+    tx: &EncodedConfirmedTransactionWithStatusMeta,
+    wallet_pubkey: &Pubkey,
+    tracked_mints: &[String],
+    tx_time: DateTime<Utc>,
+    signature: &Signature,
+    filter: &TransferFilter,
+    pre_balances: &[UiTransactionTokenBalance],
+    post_balances: &[UiTransactionTokenBalance],
+) -> Vec<Transfer> {
+    let Some(meta) = &tx.transaction.meta else {
+        return Vec::new();
+    };
+    let keys = account_keys(tx);
+    let wallet = wallet_pubkey.to_string();
+    let memo = parse_memo(tx);
+    let token_programs = [spl_token::id().to_string(), TOKEN_2022_PROGRAM_ID.to_string()];
+    let paid_fee = is_fee_payer(tx, wallet_pubkey);
+
+    // Post balances first so accounts closed in the transaction still resolve from their pre balance
+    let balance_of = |account: &str| {
+        let index = keys.iter().position(|key| *key == account)?;
+        post_balances
+            .iter()
+            .chain(pre_balances)
+            .find(|balance| usize::from(balance.account_index) == index)
+    };
+    let owner_of = |balance: Option<&UiTransactionTokenBalance>| match balance.map(|balance| &balance.owner) {
+        Some(OptionSerializer::Some(owner)) => Some(owner.clone()),
+        _ => balance
+            .filter(|balance| is_wallet_ata(tx, balance, wallet_pubkey))
+            .map(|_| wallet.clone()),
+    };
+
+    let mut transfers = Vec::new();
+    for (instruction, caller) in instructions_with_caller(tx) {
+        let UiInstruction::Parsed(UiParsedInstruction::Parsed(parsed)) = instruction else {
+            continue;
+        };
+        if !token_programs.contains(&parsed.program_id) {
+            continue;
+        }
+
+        let instruction_type = parsed.parsed["type"].as_str().unwrap_or_default();
+        let info = &parsed.parsed["info"];
+        let (Some(source), Some(destination)) = (info["source"].as_str(), info["destination"].as_str()) else {
+            continue;
+        };
+        let source_balance = balance_of(source);
+        let destination_balance = balance_of(destination);
+
+        // Plain transfers name neither the mint nor its decimals
+        let Some(balance) = source_balance.or(destination_balance) else {
+            continue;
+        };
+        let mint = info["mint"].as_str().unwrap_or(&balance.mint).to_string();
+        if !tracked_mints.contains(&mint) {
+            continue;
+        }
+        let (sent_raw, decimals) = match instruction_type {
+            "transfer" => (info["amount"].as_str(), balance.ui_token_amount.decimals),
+            "transferChecked" | "transferCheckedWithFee" => (
+                info["tokenAmount"]["amount"].as_str(),
+                info["tokenAmount"]["decimals"]
+                    .as_u64()
+                    .and_then(|decimals| u8::try_from(decimals).ok())
+                    .unwrap_or(balance.ui_token_amount.decimals),
+            ),
+            _ => continue,
+        };
+        let Some(sent_raw) = sent_raw.and_then(|amount| amount.parse::<u64>().ok()) else {
+            continue;
+        };
+
+        let source_owner = owner_of(source_balance);
+        let destination_owner = owner_of(destination_balance);
+        let from_wallet = source_owner.as_deref() == Some(wallet.as_str());
+        let to_wallet = destination_owner.as_deref() == Some(wallet.as_str());
+        let (transfer_type, token_account, counterparty, counterparty_token_account) = match (from_wallet, to_wallet) {
+            (true, true) => (TransferType::Internal, source, destination_owner, destination),
+            (true, false) => (TransferType::Sent, source, destination_owner, destination),
+            (false, true) => (TransferType::Received, destination, source_owner, source),
+            (false, false) => continue,
+        };
+        if filter.excludes_direction(&transfer_type) {
+            continue;
+        }
+
+        // The Token-2022 transfer fee is withheld from what the recipient is credited
+        let fee_raw = info["feeAmount"]["amount"]
+            .as_str()
+            .and_then(|amount| amount.parse::<u64>().ok());
+        let amount_raw = match transfer_type {
+            TransferType::Received => sent_raw.saturating_sub(fee_raw.unwrap_or(0)),
+            _ => sent_raw,
+        };
+        let scale = 10f64.powi(i32::from(decimals));
+        let amount = amount_raw as f64 / scale;
+        if (amount_raw == 0 && !filter.include_zero_amount) || filter.excludes_amount(&mint, amount) {
+            continue;
+        }
+
+        let account_initialized = keys
+            .iter()
+            .position(|key| *key == destination)
+            .is_some_and(|index| !pre_balances.iter().any(|pre| usize::from(pre.account_index) == index));
+
+        transfers.push(Transfer {
+            date: tx_time,
+            amount,
+            amount_raw,
+            decimals,
+            transfer_type,
+            signature: signature.to_string(),
+            mint,
+            success: meta.err.is_none(),
+            counterparty,
+            counterparty_token_account: Some(counterparty_token_account.to_string()),
+            memo: memo.clone(),
+            wallet: wallet.clone(),
+            token_account: token_account.to_string(),
+            fee: fee_raw.map(|fee| fee as f64 / scale),
+            gross_amount: sent_raw as f64 / scale,
+            net_amount: sent_raw.saturating_sub(fee_raw.unwrap_or(0)) as f64 / scale,
+            usd_value: None,
+            slot: tx.slot,
+            block_hash: None,
+            swap_group: None,
+            asset: None,
+            kind: instruction_kind(instruction_type),
+            commitment: None,
+            via_cpi: caller.is_some(),
+            program_id: caller.map(str::to_string),
+            fee_lamports: meta.fee,
+            paid_fee,
+            account_initialized,
+        });
     }
 
+    if transfers.is_empty() {
+        debug!("No token transfer instructions for the wallet in {}", signature);
+    }
     transfers
 }
+
+/// Whether the token account behind `balance` is `wallet_pubkey`'s
+/// associated token account for the balance's mint, under either token
+/// program. Older transactions record no balance owners, and this is the
+/// only way to attribute them from the transaction alone; other token
+/// accounts of the wallet can't be recognized without one.
+fn is_wallet_ata(
+    tx: &EncodedConfirmedTransactionWithStatusMeta,
+    balance: &UiTransactionTokenBalance,
+    wallet_pubkey: &Pubkey,
+) -> bool {
+    let (Some(account), Ok(mint)) = (account_key(tx, balance.account_index), Pubkey::from_str(&balance.mint)) else {
+        return false;
+    };
+    [spl_token::id(), TOKEN_2022_PROGRAM_ID]
+        .iter()
+        .any(|program| associated_token_address_with_program(wallet_pubkey, &mint, program).to_string() == account)
+}
+
+/// Whether `wallet_pubkey` paid the transaction fee, i.e. is the first
+/// account key. A wallet that only co-signed comes later and didn't pay.
+fn is_fee_payer(tx: &EncodedConfirmedTransactionWithStatusMeta, wallet_pubkey: &Pubkey) -> bool {
+    account_keys(tx).first().is_some_and(|payer| *payer == wallet_pubkey.to_string())
+}
+
+/// Post balances plus a zero balance for every token account that has a pre
+/// balance but none after. Closing an account in the transaction drops its
+/// post entry, so without a zero in its place the transfer that drained it
+/// would go unnoticed.
+fn with_closed_accounts(
+    pre_balances: &[UiTransactionTokenBalance],
+    post_balances: &[UiTransactionTokenBalance],
+) -> Vec<UiTransactionTokenBalance> {
+    let closed = pre_balances
+        .iter()
+        .filter(|pre| {
+            !post_balances
+                .iter()
+                .any(|post| post.account_index == pre.account_index && post.mint == pre.mint)
+        })
+        .map(|pre| {
+            let mut closed = pre.clone();
+            closed.ui_token_amount.amount = "0".to_string();
+            closed.ui_token_amount.ui_amount = Some(0.0);
+            closed.ui_token_amount.ui_amount_string = "0".to_string();
+            closed
+        });
+    post_balances.iter().cloned().chain(closed).collect()
+}
+
+/// Balance in the mint's base units. Malformed amounts count as zero.
+fn raw_amount(balance: &UiTransactionTokenBalance) -> u64 {
+    balance.ui_token_amount.amount.parse().unwrap_or(0)
+}
+
+/// Finds the pre balance matching `post`: the same account holding the same
+/// mint. `None` means the token account was created in the transaction.
+fn pre_balance<'a>(
+    pre_balances: &'a [UiTransactionTokenBalance],
+    post: &UiTransactionTokenBalance,
+) -> Option<&'a UiTransactionTokenBalance> {
+    pre_balances
+        .iter()
+        .find(|pre| pre.account_index == post.account_index && pre.mint == post.mint)
+}
+
+/// Finds the balance of the token account on the other side of a `diff`
+/// balance change for `mint`. An account that moved by exactly the opposite
+/// amount wins; otherwise the largest opposing change is used, which covers
+/// swaps routed through a pool that touch several accounts.
+fn find_counterparty<'a>(
+    pre_balances: &[UiTransactionTokenBalance],
+    post_balances: &'a [UiTransactionTokenBalance],
+    mint: &str,
+    diff: i128,
+    wallet: &str,
+) -> Option<&'a UiTransactionTokenBalance> {
+    let mut largest: Option<(u128, &UiTransactionTokenBalance)> = None;
+
+    for post in post_balances.iter().filter(|b| b.mint == mint) {
+        match &post.owner {
+            OptionSerializer::Some(owner) if owner != wallet => {}
+            _ => continue,
+        }
+
+        let pre_amount = pre_balance(pre_balances, post).map(raw_amount).unwrap_or(0);
+        let delta = i128::from(raw_amount(post)) - i128::from(pre_amount);
+
+        if delta.signum() * diff.signum() >= 0 {
+            continue; // Unchanged or moved in the same direction as our wallet
+        }
+        if delta + diff == 0 {
+            return Some(post);
+        }
+        if largest.is_none_or(|(amount, _)| delta.unsigned_abs() > amount) {
+            largest = Some((delta.unsigned_abs(), post));
+        }
+    }
+
+    largest.map(|(_, balance)| balance)
+}
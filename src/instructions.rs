@@ -0,0 +1,190 @@
+use solana_transaction_status::{
+    option_serializer::OptionSerializer, EncodedConfirmedTransactionWithStatusMeta, EncodedTransaction,
+    UiInstruction, UiMessage, UiParsedInstruction,
+};
+
+use crate::mint::TOKEN_2022_PROGRAM_ID;
+use crate::models::BalanceChangeKind;
+
+pub const MEMO_V1_PROGRAM_ID: &str = "Memo1UhkJRfHyvLMcVucJwxXeuD728EqVDDwQDxFMNo";
+pub const MEMO_V3_PROGRAM_ID: &str = "MemoSq4gqABAXKb96qnH8TuNpWk9ZYu8NB3HV8nQq8A";
+pub const WSOL_MINT: &str = "So11111111111111111111111111111111111111112";
+
+/// Returns every instruction in the transaction, top-level first, followed
+/// by the inner (CPI) instructions recorded in the status meta.
+pub fn all_instructions(tx: &EncodedConfirmedTransactionWithStatusMeta) -> Vec<&UiInstruction> {
+    instructions_with_caller(tx).into_iter().map(|(instruction, _)| instruction).collect()
+}
+
+/// Like [`all_instructions`], pairing each inner instruction with the program
+/// id of the top-level instruction whose CPI ran it. Top-level instructions
+/// are paired with `None`, as is an inner one whose caller can't be resolved.
+pub fn instructions_with_caller(
+    tx: &EncodedConfirmedTransactionWithStatusMeta,
+) -> Vec<(&UiInstruction, Option<&str>)> {
+    let mut instructions = Vec::new();
+    let mut top_level: &[UiInstruction] = &[];
+
+    if let EncodedTransaction::Json(ui_tx) = &tx.transaction.transaction {
+        if let UiMessage::Parsed(message) = &ui_tx.message {
+            top_level = &message.instructions;
+            instructions.extend(top_level.iter().map(|instruction| (instruction, None)));
+        }
+    }
+
+    if let Some(meta) = &tx.transaction.meta {
+        if let OptionSerializer::Some(inner) = &meta.inner_instructions {
+            let keys = account_keys(tx);
+            for set in inner {
+                let caller = top_level
+                    .get(usize::from(set.index))
+                    .and_then(|instruction| program_id(instruction, &keys));
+                instructions.extend(set.instructions.iter().map(|instruction| (instruction, caller)));
+            }
+        }
+    }
+
+    instructions
+}
+
+fn program_id<'a>(instruction: &'a UiInstruction, keys: &[&'a str]) -> Option<&'a str> {
+    match instruction {
+        UiInstruction::Parsed(UiParsedInstruction::Parsed(parsed)) => Some(&parsed.program_id),
+        UiInstruction::Parsed(UiParsedInstruction::PartiallyDecoded(decoded)) => Some(&decoded.program_id),
+        UiInstruction::Compiled(compiled) => keys.get(usize::from(compiled.program_id_index)).copied(),
+    }
+}
+
+/// Decodes the first SPL Memo (v1 or v3) invocation in the transaction.
+/// Memo data that isn't valid UTF-8 is ignored.
+pub fn parse_memo(tx: &EncodedConfirmedTransactionWithStatusMeta) -> Option<String> {
+    all_instructions(tx).into_iter().find_map(|instruction| match instruction {
+        UiInstruction::Parsed(UiParsedInstruction::Parsed(parsed))
+            if is_memo_program(&parsed.program_id) =>
+        {
+            parsed.parsed.as_str().map(str::to_string)
+        }
+        UiInstruction::Parsed(UiParsedInstruction::PartiallyDecoded(decoded))
+            if is_memo_program(&decoded.program_id) =>
+        {
+            let data = bs58::decode(&decoded.data).into_vec().ok()?;
+            String::from_utf8(data).ok()
+        }
+        _ => None,
+    })
+}
+
+fn is_memo_program(program_id: &str) -> bool {
+    program_id == MEMO_V1_PROGRAM_ID || program_id == MEMO_V3_PROGRAM_ID
+}
+
+/// Lists the transaction's account keys in balance order, including
+/// addresses loaded from lookup tables by v0 transactions. Parsed messages
+/// list those inline; raw messages carry only the static keys, so the
+/// loaded writable and then readonly addresses from the status meta follow.
+pub fn account_keys(tx: &EncodedConfirmedTransactionWithStatusMeta) -> Vec<&str> {
+    match &tx.transaction.transaction {
+        EncodedTransaction::Json(ui_tx) => match &ui_tx.message {
+            UiMessage::Parsed(message) => message.account_keys.iter().map(|key| key.pubkey.as_str()).collect(),
+            UiMessage::Raw(message) => {
+                let mut keys: Vec<&str> = message.account_keys.iter().map(String::as_str).collect();
+                if let Some(meta) = &tx.transaction.meta {
+                    if let OptionSerializer::Some(loaded) = &meta.loaded_addresses {
+                        keys.extend(loaded.writable.iter().chain(&loaded.readonly).map(String::as_str));
+                    }
+                }
+                keys
+            }
+        },
+        _ => Vec::new(),
+    }
+}
+
+/// Resolves the account address at `index` in the transaction's account keys.
+pub fn account_key(tx: &EncodedConfirmedTransactionWithStatusMeta, index: u8) -> Option<&str> {
+    account_keys(tx).get(index as usize).copied()
+}
+
+/// Sums the Token-2022 transfer fees withheld on `transferCheckedWithFee`
+/// instructions that debit or credit `token_account`. Returns `None` when no
+/// such instruction touches the account.
+pub fn withheld_fee(tx: &EncodedConfirmedTransactionWithStatusMeta, token_account: &str) -> Option<f64> {
+    all_instructions(tx)
+        .into_iter()
+        .filter_map(|instruction| match instruction {
+            UiInstruction::Parsed(UiParsedInstruction::Parsed(parsed)) => Some(&parsed.parsed),
+            _ => None,
+        })
+        .filter(|parsed| parsed["type"] == "transferCheckedWithFee")
+        .map(|parsed| &parsed["info"])
+        .filter(|info| info["source"] == token_account || info["destination"] == token_account)
+        .map(|info| info["feeAmount"]["uiAmount"].as_f64().unwrap_or(0.0))
+        .reduce(|total, fee| total + fee)
+}
+
+/// Whether a parsed token instruction of one of `instruction_types` (e.g.
+/// `closeAccount`, `syncNative`) operates on `token_account`.
+pub fn has_account_instruction(
+    tx: &EncodedConfirmedTransactionWithStatusMeta,
+    token_account: &str,
+    instruction_types: &[&str],
+) -> bool {
+    all_instructions(tx).into_iter().any(|instruction| match instruction {
+        UiInstruction::Parsed(UiParsedInstruction::Parsed(parsed)) => {
+            instruction_types.iter().any(|t| parsed.parsed["type"] == *t)
+                && parsed.parsed["info"]["account"] == token_account
+        }
+        _ => false,
+    })
+}
+
+/// Classifies the first parsed token instruction that moves the balance of
+/// `token_account`: a transfer into or out of it, a mint to it, a burn from
+/// it or its closing. Returns `None` when no such instruction is found, for
+/// example when the moving program's instructions weren't parsed.
+pub fn balance_change_kind(
+    tx: &EncodedConfirmedTransactionWithStatusMeta,
+    token_account: &str,
+) -> Option<BalanceChangeKind> {
+    balance_change(tx, token_account).map(|(kind, _)| kind)
+}
+
+/// Like [`balance_change_kind`], also returning the program that invoked the
+/// instruction through CPI, or `None` when it was a top-level instruction.
+pub fn balance_change<'a>(
+    tx: &'a EncodedConfirmedTransactionWithStatusMeta,
+    token_account: &str,
+) -> Option<(BalanceChangeKind, Option<&'a str>)> {
+    let token_programs = [spl_token::id().to_string(), TOKEN_2022_PROGRAM_ID.to_string()];
+    instructions_with_caller(tx).into_iter().find_map(|(instruction, caller)| {
+        let UiInstruction::Parsed(UiParsedInstruction::Parsed(parsed)) = instruction else {
+            return None;
+        };
+        if !token_programs.contains(&parsed.program_id) {
+            return None;
+        }
+        let info = &parsed.parsed["info"];
+        let kind = parsed.parsed["type"].as_str().and_then(instruction_kind)?;
+        let touches = match kind {
+            BalanceChangeKind::Transfer | BalanceChangeKind::TransferChecked => {
+                info["source"] == token_account || info["destination"] == token_account
+            }
+            BalanceChangeKind::MintTo | BalanceChangeKind::Burn | BalanceChangeKind::CloseAccount => {
+                info["account"] == token_account
+            }
+        };
+        touches.then_some((kind, caller))
+    })
+}
+
+/// Maps a parsed token instruction type to the balance change it makes.
+pub(crate) fn instruction_kind(instruction_type: &str) -> Option<BalanceChangeKind> {
+    match instruction_type {
+        "transfer" => Some(BalanceChangeKind::Transfer),
+        "transferChecked" | "transferCheckedWithFee" => Some(BalanceChangeKind::TransferChecked),
+        "mintTo" | "mintToChecked" => Some(BalanceChangeKind::MintTo),
+        "burn" | "burnChecked" => Some(BalanceChangeKind::Burn),
+        "closeAccount" => Some(BalanceChangeKind::CloseAccount),
+        _ => None,
+    }
+}
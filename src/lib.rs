@@ -0,0 +1,26 @@
+pub mod archive;
+#[cfg(feature = "blocking")]
+pub mod blocking;
+pub mod blocks;
+pub mod cache;
+pub mod client;
+pub mod config;
+pub mod error;
+pub mod export;
+pub mod gaps;
+pub mod indexer;
+pub mod instructions;
+pub mod live;
+pub mod mint;
+pub mod models;
+pub mod pool;
+pub mod prices;
+pub mod rate_limit;
+pub mod resume;
+pub mod retry;
+mod seek;
+pub mod sink;
+pub mod sol;
+pub mod source;
+pub mod summary;
+pub mod time;
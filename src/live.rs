@@ -0,0 +1,246 @@
+use chrono::{TimeZone, Utc};
+use futures::channel::mpsc;
+use futures::stream::{self, Stream, StreamExt};
+use futures::SinkExt;
+use solana_client::nonblocking::pubsub_client::PubsubClient;
+use solana_client::rpc_config::{RpcTransactionLogsConfig, RpcTransactionLogsFilter};
+use solana_sdk::{commitment_config::CommitmentConfig, pubkey::Pubkey, signature::Signature};
+use std::collections::{HashSet, VecDeque};
+use std::str::FromStr;
+use std::sync::Arc;
+use std::time::Duration;
+use tracing::{info, warn};
+
+use crate::error::IndexerError;
+use crate::indexer::{fetch_signatures, fetch_transaction, process_transaction, TransferFilter, DEFAULT_SIGNATURE_PAGE_LIMIT};
+use crate::mint::{associated_token_address_with_program, TOKEN_2022_PROGRAM_ID};
+use crate::models::Transfer;
+use crate::retry::RetryPolicy;
+use crate::source::SolanaSource;
+
+/// Transfers buffered for a slow consumer before the subscription waits.
+const CHANNEL_CAPACITY: usize = 256;
+
+/// Upper bound on the delay between reconnection attempts.
+const MAX_RECONNECT_DELAY: Duration = Duration::from_secs(30);
+
+/// Signatures remembered to drop repeat notifications, since one transaction
+/// mentions several of the subscribed addresses.
+const RECENT_SIGNATURES: usize = 4096;
+
+/// Streams transfers for `wallet` live as transactions land, by subscribing
+/// over the WebSocket endpoint `ws_url` to logs mentioning the wallet and its
+/// associated token accounts for each of `mints` (under both SPL Token and
+/// Token-2022). Each new signature is fetched through `client` and processed
+/// with `filter`, as the historical indexer does.
+///
+/// The subscription runs on a spawned task until the stream is dropped. On
+/// disconnect it reconnects with exponential backoff starting at
+/// `retry.base_delay`, then backfills the signatures that landed in the gap
+/// before resuming. Fetch failures are logged and the transaction skipped.
+/// `commitment` must be at least `confirmed`, as `getTransaction` does not
+/// serve `processed` transactions. Token accounts other than the associated
+/// ones aren't watched.
+pub fn subscribe_token_transfers<S>(
+    client: Arc<S>,
+    ws_url: impl Into<String>,
+    wallet: &str,
+    mints: &[Pubkey],
+    retry: RetryPolicy,
+    filter: TransferFilter,
+    commitment: CommitmentConfig,
+) -> Result<impl Stream<Item = Transfer>, IndexerError>
+where
+    S: SolanaSource + Send + Sync + 'static,
+{
+    let wallet_pubkey =
+        Pubkey::from_str(wallet).map_err(|_| IndexerError::InvalidPubkey(wallet.to_string()))?;
+
+    let mut addresses = vec![wallet_pubkey];
+    for mint in mints {
+        for token_program in [spl_token::id(), TOKEN_2022_PROGRAM_ID] {
+            addresses.push(associated_token_address_with_program(&wallet_pubkey, mint, &token_program));
+        }
+    }
+
+    let (sender, receiver) = mpsc::channel(CHANNEL_CAPACITY);
+    let subscription = Subscription {
+        client,
+        ws_url: ws_url.into(),
+        wallet_pubkey,
+        mints: mints.to_vec(),
+        addresses,
+        retry,
+        filter,
+        commitment,
+        sender,
+        recent: RecentSignatures::default(),
+    };
+    tokio::spawn(subscription.run());
+    Ok(receiver)
+}
+
+struct Subscription<S> {
+    client: Arc<S>,
+    ws_url: String,
+    wallet_pubkey: Pubkey,
+    mints: Vec<Pubkey>,
+    addresses: Vec<Pubkey>,
+    retry: RetryPolicy,
+    filter: TransferFilter,
+    commitment: CommitmentConfig,
+    sender: mpsc::Sender<Transfer>,
+    recent: RecentSignatures,
+}
+
+impl<S: SolanaSource + Send + Sync + 'static> Subscription<S> {
+    async fn run(mut self) {
+        let mut last_signature = None;
+        let mut delay = self.retry.base_delay;
+
+        while !self.sender.is_closed() {
+            match self.listen(&mut last_signature, &mut delay).await {
+                Ok(()) => warn!("Log subscription for {} ended, reconnecting", self.wallet_pubkey),
+                Err(Closed) => return,
+            }
+            tokio::time::sleep(delay).await;
+            delay = (delay * 2).min(MAX_RECONNECT_DELAY);
+        }
+    }
+
+    /// Subscribes and forwards transfers until the connection drops.
+    async fn listen(&mut self, last_signature: &mut Option<Signature>, delay: &mut Duration) -> Result<(), Closed> {
+        let pubsub = match PubsubClient::new(&self.ws_url).await {
+            Ok(pubsub) => pubsub,
+            Err(e) => {
+                warn!("Failed to connect to {}: {}", self.ws_url, e);
+                return Ok(());
+            }
+        };
+
+        let mut subscriptions = Vec::with_capacity(self.addresses.len());
+        for address in &self.addresses {
+            let config = RpcTransactionLogsConfig {
+                commitment: Some(self.commitment),
+            };
+            match pubsub
+                .logs_subscribe(RpcTransactionLogsFilter::Mentions(vec![address.to_string()]), config)
+                .await
+            {
+                Ok((logs, _unsubscribe)) => subscriptions.push(logs),
+                Err(e) => {
+                    warn!("Failed to subscribe to logs for {}: {}", address, e);
+                    return Ok(());
+                }
+            }
+        }
+        info!("Subscribed to logs for {} addresses", subscriptions.len());
+        *delay = self.retry.base_delay;
+
+        if let Some(until) = *last_signature {
+            self.backfill(until, last_signature).await?;
+        }
+
+        let mut logs = stream::select_all(subscriptions);
+        while let Some(response) = logs.next().await {
+            let Ok(signature) = Signature::from_str(&response.value.signature) else {
+                continue;
+            };
+            *last_signature = Some(signature);
+            self.deliver(signature).await?;
+        }
+        Ok(())
+    }
+
+    /// Delivers, oldest first, the signatures newer than `until` on any
+    /// watched address, which were missed while disconnected.
+    async fn backfill(&mut self, until: Signature, last_signature: &mut Option<Signature>) -> Result<(), Closed> {
+        let mut missed = Vec::new();
+        for address in &self.addresses {
+            match fetch_signatures(
+                self.client.as_ref(),
+                address,
+                chrono::DateTime::<Utc>::MIN_UTC,
+                None,
+                Some(until),
+                DEFAULT_SIGNATURE_PAGE_LIMIT,
+                &self.retry,
+                self.commitment,
+                None,
+            )
+            .await
+            {
+                Ok(signatures) => missed.extend(signatures),
+                Err(e) => warn!("Failed to backfill signatures for {}: {}", address, e),
+            }
+        }
+
+        missed.sort_by_key(|sig_info| sig_info.slot);
+        info!("Backfilling {} signatures since {}", missed.len(), until);
+        for sig_info in missed {
+            let Ok(signature) = Signature::from_str(&sig_info.signature) else {
+                continue;
+            };
+            *last_signature = Some(signature);
+            self.deliver(signature).await?;
+        }
+        Ok(())
+    }
+
+    async fn deliver(&mut self, signature: Signature) -> Result<(), Closed> {
+        if !self.recent.insert(signature) {
+            return Ok(());
+        }
+
+        let tx = match fetch_transaction(self.client.as_ref(), &signature, &self.retry, self.commitment).await {
+            Ok(tx) => tx,
+            Err(e) => {
+                warn!("Failed to get transaction {}: {}", signature, e);
+                return Ok(());
+            }
+        };
+        let tx_time = tx
+            .block_time
+            .and_then(|t| Utc.timestamp_opt(t, 0).single())
+            .unwrap_or_else(Utc::now);
+
+        let transfers =
+            match process_transaction(&tx, &self.wallet_pubkey, &self.mints, tx_time, &signature, &self.filter) {
+                Ok(transfers) => transfers,
+                Err(e) => {
+                    warn!("Failed to process transaction {}: {}", signature, e);
+                    return Ok(());
+                }
+            };
+        for transfer in transfers {
+            self.sender.send(transfer).await.map_err(|_| Closed)?;
+        }
+        Ok(())
+    }
+}
+
+/// The consumer dropped the stream.
+struct Closed;
+
+/// Bounded set of recently delivered signatures.
+#[derive(Default)]
+struct RecentSignatures {
+    set: HashSet<Signature>,
+    order: VecDeque<Signature>,
+}
+
+impl RecentSignatures {
+    /// Returns `false` if `signature` was already seen.
+    fn insert(&mut self, signature: Signature) -> bool {
+        if !self.set.insert(signature) {
+            return false;
+        }
+        self.order.push_back(signature);
+        if self.order.len() > RECENT_SIGNATURES {
+            if let Some(oldest) = self.order.pop_front() {
+                self.set.remove(&oldest);
+            }
+        }
+        true
+    }
+}
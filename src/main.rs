@@ -1,15 +1,13 @@
-use actix_web::{App, HttpServer, HttpResponse, web::Responder, web as actix_web};
-use chrono::{Duration, Utc};
-use solana_client::nonblocking::rpc_client::RpcClient;
+use actix_web::{App, HttpServer, HttpResponse, Responder};
 use std::env;
-use log::{error, info};
+use log::{error, info, warn};
 
-mod indexer;
-mod models;
 mod web;
 
-use indexer::index_usdc_transfers;
-use web::get_transfers;
+use solana_usdc_indexer::client::RpcClientConfig;
+use solana_usdc_indexer::config::IndexerConfig;
+use solana_usdc_indexer::time::TimeRange;
+use web::{get_transfers, get_transfers_csv};
 
 async fn root() -> impl Responder {
     HttpResponse::TemporaryRedirect()
@@ -23,18 +21,30 @@ async fn main() -> std::io::Result<()> {
     
     let rpc_url = env::var("SOLANA_RPC_URL").unwrap_or("https://api.mainnet-beta.solana.com".to_string());
     info!("Using RPC URL: {}", rpc_url);
-    let client = RpcClient::new(rpc_url);
+    let client = RpcClientConfig::default()
+        .connect(rpc_url)
+        .map_err(|e| std::io::Error::other(e.to_string()))?;
     
     let wallet = "7cMEhpt9y3inBNVv8fNnuaEbx7hKHZnLvR1KWKKxuDDU".to_string();
-    let usdc_mint = "EPjFWdd5AufqSSqeM2qN1xzybapC8G4wEGGkZwyTDt1v".to_string();
     
-    let end_time = Utc::now();
-    let start_time = end_time - Duration::hours(96); // 96 hours per user change
+    let config = IndexerConfig::new(wallet).time_range(TimeRange::last_days(4)); // 96 hours per user change
     
-    let transfers = match index_usdc_transfers(&client, &wallet, &usdc_mint, start_time, end_time).await {
-        Ok(transfers) => {
-            info!("Successfully indexed {} transfers", transfers.len());
-            transfers
+    let transfers = match config.run(&client).await {
+        Ok(result) => {
+            for (signature, e) in &result.errors {
+                warn!("Could not fetch transaction {}: {}", signature, e);
+            }
+            if result.skipped_no_block_time > 0 {
+                warn!("Skipped {} transactions with no block time", result.skipped_no_block_time);
+            }
+            info!(
+                "Successfully indexed {} transfers ({} RPC calls, {} of {} signatures in range)",
+                result.transfers.len(),
+                result.stats.rpc_calls,
+                result.stats.in_range,
+                result.stats.signatures_fetched
+            );
+            result.transfers
         }
         Err(e) => {
             error!("Failed to index transfers: {}", e);
@@ -44,9 +54,10 @@ async fn main() -> std::io::Result<()> {
     
     HttpServer::new(move || {
         App::new()
-            .app_data(actix_web::Data::new(transfers.clone()))
-            .route("/", actix_web::get().to(root))
-            .route("/transfers", actix_web::get().to(get_transfers))
+            .app_data(actix_web::web::Data::new(transfers.clone()))
+            .route("/", actix_web::web::get().to(root))
+            .route("/transfers", actix_web::web::get().to(get_transfers))
+            .route("/transfers.csv", actix_web::web::get().to(get_transfers_csv))
     })
     .bind(("0.0.0.0", 8080))?
     .workers(4)
@@ -0,0 +1,108 @@
+use solana_sdk::{commitment_config::CommitmentConfig, pubkey, pubkey::Pubkey};
+use spl_associated_token_account_client::address::get_associated_token_address_with_program_id;
+use spl_token::solana_program::program_pack::Pack;
+use spl_token::state::{Account as TokenAccount, Mint};
+use tracing::warn;
+
+use crate::error::IndexerError;
+use crate::retry::{retry_with_backoff, RetryPolicy};
+use crate::source::SolanaSource;
+
+pub const TOKEN_2022_PROGRAM_ID: Pubkey = pubkey!("TokenzQdBNbLqP5VEhdkAS6EPFLC1PHnBqCXEpPxuEb");
+
+/// Offset of the account type byte Token-2022 appends to accounts with
+/// extensions, just past the size of a base token account.
+const TOKEN_2022_ACCOUNT_TYPE_OFFSET: usize = spl_token::state::Account::LEN;
+
+/// Token-2022 account type marking a mint.
+const TOKEN_2022_MINT_ACCOUNT_TYPE: u8 = 1;
+
+/// Checks that `mint` is an initialized mint owned by the Token or
+/// Token-2022 program, failing with [`IndexerError::NotAMint`] otherwise.
+/// Catches a token account or wallet address passed by mistake, which would
+/// otherwise index silently to zero transfers.
+pub async fn validate_mint(
+    client: &(impl SolanaSource + Sync),
+    mint: &Pubkey,
+    retry: &RetryPolicy,
+    commitment: CommitmentConfig,
+) -> Result<(), IndexerError> {
+    let account = retry_with_backoff(retry, || client.get_account_with_commitment(mint, commitment))
+        .await?
+        .value;
+    let Some(account) = account else {
+        warn!("Mint account {} does not exist", mint);
+        return Err(IndexerError::NotAMint(mint.to_string()));
+    };
+
+    let data = &account.data;
+    let is_mint_layout = if account.owner == spl_token::id() {
+        data.len() == Mint::LEN
+    } else if account.owner == TOKEN_2022_PROGRAM_ID {
+        data.len() == Mint::LEN
+            || data.get(TOKEN_2022_ACCOUNT_TYPE_OFFSET) == Some(&TOKEN_2022_MINT_ACCOUNT_TYPE)
+    } else {
+        false
+    };
+    let is_mint = is_mint_layout
+        && Mint::unpack_from_slice(&data[..Mint::LEN]).is_ok_and(|mint| mint.is_initialized);
+
+    if is_mint {
+        Ok(())
+    } else {
+        warn!("Account {} owned by {} is not a mint", mint, account.owner);
+        Err(IndexerError::NotAMint(mint.to_string()))
+    }
+}
+
+/// Current balance of `wallet`'s associated token account for `mint`, in
+/// whole tokens, or `0.0` if the account doesn't exist. The mint is fetched
+/// first for its decimals and token program, so this takes two RPC calls.
+/// Tokens held in other token accounts of the wallet aren't counted.
+pub async fn current_balance(
+    client: &(impl SolanaSource + Sync),
+    wallet: &Pubkey,
+    mint: &Pubkey,
+    retry: &RetryPolicy,
+    commitment: CommitmentConfig,
+) -> Result<f64, IndexerError> {
+    let mint_account = retry_with_backoff(retry, || client.get_account_with_commitment(mint, commitment))
+        .await?
+        .value
+        .filter(|account| account.owner == spl_token::id() || account.owner == TOKEN_2022_PROGRAM_ID)
+        .ok_or_else(|| IndexerError::NotAMint(mint.to_string()))?;
+    let decimals = mint_account
+        .data
+        .get(..Mint::LEN)
+        .and_then(|data| Mint::unpack_from_slice(data).ok())
+        .ok_or_else(|| IndexerError::NotAMint(mint.to_string()))?
+        .decimals;
+
+    let ata = associated_token_address_with_program(wallet, mint, &mint_account.owner);
+    let account = retry_with_backoff(retry, || client.get_account_with_commitment(&ata, commitment))
+        .await?
+        .value;
+    // Token-2022 accounts with extensions share the base layout up front
+    let amount = account
+        .and_then(|account| TokenAccount::unpack_from_slice(account.data.get(..TokenAccount::LEN)?).ok())
+        .map_or(0, |account| account.amount);
+    Ok(amount as f64 / 10f64.powi(i32::from(decimals)))
+}
+
+/// Derives the associated token account of `wallet` for `mint` under the
+/// SPL Token program, the account most wallets receive into.
+pub fn associated_token_address(wallet: &Pubkey, mint: &Pubkey) -> Pubkey {
+    associated_token_address_with_program(wallet, mint, &spl_token::id())
+}
+
+/// Derives the associated token account of `wallet` for a Token-2022 `mint`.
+pub fn associated_token_address_2022(wallet: &Pubkey, mint: &Pubkey) -> Pubkey {
+    associated_token_address_with_program(wallet, mint, &TOKEN_2022_PROGRAM_ID)
+}
+
+/// Derives the associated token account of `wallet` for `mint` under
+/// `token_program` (SPL Token or Token-2022). The address differs between
+/// the two, so pass the program that owns the mint.
+pub fn associated_token_address_with_program(wallet: &Pubkey, mint: &Pubkey, token_program: &Pubkey) -> Pubkey {
+    get_associated_token_address_with_program_id(wallet, mint, token_program)
+}
@@ -1,16 +1,110 @@
 use chrono::{DateTime, Utc};
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
+use solana_sdk::commitment_config::CommitmentLevel;
 
-#[derive(Clone, Serialize, Debug)]
+#[derive(Clone, Serialize, Deserialize, Debug, PartialEq, Eq, Hash)]
+#[serde(rename_all = "lowercase")]
 pub enum TransferType {
     Sent,
     Received,
+    Internal,
+    /// The balance changed but whose account it is couldn't be determined,
+    /// so neither direction applies. Only emitted with
+    /// [`crate::indexer::TransferFilter::include_unknown`].
+    Unknown,
 }
 
-#[derive(Clone, Serialize)]
+/// The token instruction behind a balance change. Tokens minted or burned
+/// change the supply rather than moving between holders, so they are
+/// reported apart from transfers.
+#[derive(Clone, Copy, Serialize, Deserialize, Debug, PartialEq, Eq, Hash)]
+#[serde(rename_all = "camelCase")]
+pub enum BalanceChangeKind {
+    Transfer,
+    /// `transferChecked`, or Token-2022's `transferCheckedWithFee`.
+    TransferChecked,
+    /// `mintTo` or `mintToChecked`.
+    MintTo,
+    /// `burn` or `burnChecked`.
+    Burn,
+    CloseAccount,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
 pub struct Transfer {
     pub date: DateTime<Utc>,
     pub amount: f64,
+    pub amount_raw: u64,
+    pub decimals: u8,
     pub transfer_type: TransferType,
     pub signature: String,
-}
\ No newline at end of file
+    pub mint: String,
+    pub success: bool,
+    pub counterparty: Option<String>,
+    pub counterparty_token_account: Option<String>,
+    pub memo: Option<String>,
+    pub wallet: String,
+    pub token_account: String,
+    pub fee: Option<f64>,
+    /// What the sender was debited. Differs from `net_amount` only for
+    /// Token-2022 mints with a transfer fee, by the withheld `fee`.
+    pub gross_amount: f64,
+    /// What the receiver was credited.
+    pub net_amount: f64,
+    pub usd_value: Option<f64>,
+    pub slot: u64,
+    pub block_hash: Option<String>,
+    pub swap_group: Option<String>,
+    /// Logical asset the mint belongs to, when several mints are tracked as
+    /// one (such as before and after a token migration).
+    pub asset: Option<String>,
+    /// The instruction that changed the balance, if a parsed token
+    /// instruction on the account was found.
+    pub kind: Option<BalanceChangeKind>,
+    /// Commitment the transaction was fetched at. Lower than the run's when
+    /// it came from [`crate::indexer::TransferFilter::commitment_fallback`],
+    /// so it may still be rolled back; `None` where it wasn't recorded.
+    pub commitment: Option<CommitmentLevel>,
+    /// The token instruction that moved the balance ran as an inner
+    /// instruction, through a CPI from another program such as a lending
+    /// protocol, rather than directly from the transaction.
+    pub via_cpi: bool,
+    /// The program whose CPI moved the balance, when `via_cpi`.
+    pub program_id: Option<String>,
+    pub fee_lamports: u64,
+    pub paid_fee: bool,
+    pub account_initialized: bool,
+}
+
+/// Filtering and totals over a slice of transfers. Amounts are summed as is,
+/// so narrow the slice to a single mint first when several are mixed.
+pub trait TransferExt {
+    fn received(&self) -> impl Iterator<Item = &Transfer>;
+    fn sent(&self) -> impl Iterator<Item = &Transfer>;
+    fn total_received(&self) -> f64;
+    fn total_sent(&self) -> f64;
+    /// Received minus sent. Internal transfers don't count either way.
+    fn net(&self) -> f64;
+}
+
+impl TransferExt for [Transfer] {
+    fn received(&self) -> impl Iterator<Item = &Transfer> {
+        self.iter().filter(|t| t.transfer_type == TransferType::Received)
+    }
+
+    fn sent(&self) -> impl Iterator<Item = &Transfer> {
+        self.iter().filter(|t| t.transfer_type == TransferType::Sent)
+    }
+
+    fn total_received(&self) -> f64 {
+        self.received().map(|t| t.amount).sum()
+    }
+
+    fn total_sent(&self) -> f64 {
+        self.sent().map(|t| t.amount).sum()
+    }
+
+    fn net(&self) -> f64 {
+        self.total_received() - self.total_sent()
+    }
+}
@@ -0,0 +1,171 @@
+use async_trait::async_trait;
+use solana_client::client_error::Result as ClientResult;
+use solana_client::rpc_client::GetConfirmedSignaturesForAddress2Config;
+use solana_client::rpc_config::{RpcBlockConfig, RpcTransactionConfig};
+use solana_client::rpc_response::{RpcConfirmedTransactionStatusWithSignature, RpcResult};
+use solana_sdk::{
+    account::Account, clock::Slot, clock::UnixTimestamp, commitment_config::CommitmentConfig, pubkey::Pubkey,
+    signature::Signature,
+};
+use solana_transaction_status::{EncodedConfirmedTransactionWithStatusMeta, TransactionStatus, UiConfirmedBlock};
+use std::future::Future;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use tracing::warn;
+
+use crate::retry::is_transient;
+use crate::source::SolanaSource;
+
+/// How long a source that failed with a transient error is tried last.
+const UNHEALTHY_COOLDOWN: Duration = Duration::from_secs(30);
+
+/// A [`SolanaSource`] spreading calls round-robin over several sources, such
+/// as `RpcClient`s for different providers. A call that fails with a
+/// transient error (timeout, connection failure, 429 or 5xx) is retried on
+/// the next source, and the failing one is tried last for a while, so a
+/// provider outage mid-scan doesn't end the run. Other errors are returned
+/// as is. Only once every source has failed is the last error returned, to
+/// the caller's own [`crate::retry::RetryPolicy`].
+pub struct ClientPool<S> {
+    sources: Vec<S>,
+    unhealthy_until: Vec<Mutex<Option<Instant>>>,
+    next: AtomicUsize,
+}
+
+impl<S> ClientPool<S> {
+    /// Panics if `sources` is empty.
+    pub fn new(sources: Vec<S>) -> Self {
+        assert!(!sources.is_empty(), "ClientPool needs at least one source");
+        let unhealthy_until = sources.iter().map(|_| Mutex::new(None)).collect();
+        ClientPool {
+            sources,
+            unhealthy_until,
+            next: AtomicUsize::new(0),
+        }
+    }
+
+    /// Source indices in the order to try them for one call: round-robin
+    /// from the next source, healthy sources first.
+    fn order(&self) -> Vec<usize> {
+        let len = self.sources.len();
+        let start = self.next.fetch_add(1, Ordering::Relaxed) % len;
+        let now = Instant::now();
+        let (mut healthy, unhealthy): (Vec<usize>, Vec<usize>) = (0..len)
+            .map(|offset| (start + offset) % len)
+            .partition(|&index| self.is_healthy(index, now));
+        healthy.extend(unhealthy);
+        healthy
+    }
+
+    fn is_healthy(&self, index: usize, now: Instant) -> bool {
+        let until = self.unhealthy_until[index].lock().unwrap_or_else(|e| e.into_inner());
+        until.is_none_or(|until| now >= until)
+    }
+
+    fn mark_unhealthy(&self, index: usize) {
+        let mut until = self.unhealthy_until[index].lock().unwrap_or_else(|e| e.into_inner());
+        *until = Some(Instant::now() + UNHEALTHY_COOLDOWN);
+    }
+
+    async fn failover<'a, T, Fut>(&'a self, op: impl Fn(&'a S) -> Fut) -> ClientResult<T>
+    where
+        Fut: Future<Output = ClientResult<T>>,
+    {
+        let order = self.order();
+        let mut last_error = None;
+        for (attempt, &index) in order.iter().enumerate() {
+            match op(&self.sources[index]).await {
+                Err(e) if is_transient(&e) && attempt + 1 < order.len() => {
+                    warn!("RPC source {} failed, failing over: {}", index, e);
+                    self.mark_unhealthy(index);
+                    last_error = Some(e);
+                }
+                result => return result,
+            }
+        }
+        Err(last_error.expect("pool has at least one source"))
+    }
+}
+
+#[async_trait]
+impl<S: SolanaSource + Sync + Send> SolanaSource for ClientPool<S> {
+    async fn get_signatures_for_address_with_config(
+        &self,
+        address: &Pubkey,
+        config: GetConfirmedSignaturesForAddress2Config,
+    ) -> ClientResult<Vec<RpcConfirmedTransactionStatusWithSignature>> {
+        self.failover(|source| {
+            let config = GetConfirmedSignaturesForAddress2Config {
+                before: config.before,
+                until: config.until,
+                limit: config.limit,
+                commitment: config.commitment,
+            };
+            source.get_signatures_for_address_with_config(address, config)
+        })
+        .await
+    }
+
+    async fn get_transaction_with_config(
+        &self,
+        signature: &Signature,
+        config: RpcTransactionConfig,
+    ) -> ClientResult<EncodedConfirmedTransactionWithStatusMeta> {
+        self.failover(|source| source.get_transaction_with_config(signature, config)).await
+    }
+
+    async fn get_transactions_with_config(
+        &self,
+        signatures: &[Signature],
+        config: RpcTransactionConfig,
+    ) -> Vec<ClientResult<EncodedConfirmedTransactionWithStatusMeta>> {
+        // Only a batch that failed outright moves on; entries missing from a
+        // partial reply are refetched one by one by the indexer, failing
+        // over individually
+        let order = self.order();
+        let mut results = Vec::new();
+        for (attempt, &index) in order.iter().enumerate() {
+            results = self.sources[index].get_transactions_with_config(signatures, config).await;
+            let failed = !results.is_empty()
+                && results.iter().all(|result| result.as_ref().is_err_and(is_transient));
+            if !failed || attempt + 1 == order.len() {
+                break;
+            }
+            warn!("RPC source {} failed a batch of {} transactions, failing over", index, signatures.len());
+            self.mark_unhealthy(index);
+        }
+        results
+    }
+
+    async fn get_signature_statuses_with_history(
+        &self,
+        signatures: &[Signature],
+    ) -> RpcResult<Vec<Option<TransactionStatus>>> {
+        self.failover(|source| source.get_signature_statuses_with_history(signatures)).await
+    }
+
+    async fn get_account_with_commitment(
+        &self,
+        pubkey: &Pubkey,
+        commitment: CommitmentConfig,
+    ) -> RpcResult<Option<Account>> {
+        self.failover(|source| source.get_account_with_commitment(pubkey, commitment)).await
+    }
+
+    async fn get_slot_with_commitment(&self, commitment: CommitmentConfig) -> ClientResult<Slot> {
+        self.failover(|source| source.get_slot_with_commitment(commitment)).await
+    }
+
+    async fn get_block_time(&self, slot: Slot) -> ClientResult<UnixTimestamp> {
+        self.failover(|source| source.get_block_time(slot)).await
+    }
+
+    async fn get_block_with_config(&self, slot: Slot, config: RpcBlockConfig) -> ClientResult<UiConfirmedBlock> {
+        self.failover(|source| source.get_block_with_config(slot, config)).await
+    }
+
+    fn cache_hits(&self) -> usize {
+        self.sources.iter().map(SolanaSource::cache_hits).sum()
+    }
+}
@@ -0,0 +1,51 @@
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use solana_sdk::{pubkey, pubkey::Pubkey};
+use std::str::FromStr;
+use tracing::warn;
+
+use crate::error::IndexerError;
+use crate::models::Transfer;
+
+const STABLECOIN_MINTS: [Pubkey; 3] = [
+    pubkey!("EPjFWdd5AufqSSqeM2qN1xzybapC8G4wEGGkZwyTDt1v"), // USDC
+    pubkey!("Es9vMFrzaCERmJfrF4H2FYD4KCoNkY11McCe8BenwNYB"), // USDT
+    pubkey!("2b1kV6DkPAnxd5ixfnxCpjxmKwqjjaYmCZfHsFu24GXo"), // PYUSD
+];
+
+/// Source of historical USD prices for a mint.
+#[async_trait]
+pub trait PriceProvider {
+    async fn price_at(&self, mint: &Pubkey, time: DateTime<Utc>) -> Result<f64, IndexerError>;
+}
+
+/// Prices the well-known USD stablecoins at exactly $1 and fails for
+/// anything else.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct StablecoinPriceProvider;
+
+#[async_trait]
+impl PriceProvider for StablecoinPriceProvider {
+    async fn price_at(&self, mint: &Pubkey, _time: DateTime<Utc>) -> Result<f64, IndexerError> {
+        if STABLECOIN_MINTS.contains(mint) {
+            Ok(1.0)
+        } else {
+            Err(IndexerError::PriceUnavailable(mint.to_string()))
+        }
+    }
+}
+
+/// Sets `usd_value` on each transfer from the price of its mint at the
+/// transfer's date. Transfers the provider can't price are left as `None`.
+pub async fn enrich_with_prices(transfers: &mut [Transfer], provider: &(impl PriceProvider + Sync)) {
+    for t in transfers.iter_mut() {
+        let Ok(mint) = Pubkey::from_str(&t.mint) else {
+            warn!("Cannot price transfer {} with invalid mint {}", t.signature, t.mint);
+            continue;
+        };
+        match provider.price_at(&mint, t.date).await {
+            Ok(price) => t.usd_value = Some(t.amount * price),
+            Err(e) => warn!("No USD price for transfer {}: {}", t.signature, e),
+        }
+    }
+}
@@ -0,0 +1,147 @@
+use async_trait::async_trait;
+use solana_client::client_error::Result as ClientResult;
+use solana_client::rpc_client::GetConfirmedSignaturesForAddress2Config;
+use solana_client::rpc_config::{RpcBlockConfig, RpcTransactionConfig};
+use solana_client::rpc_response::{RpcConfirmedTransactionStatusWithSignature, RpcResult};
+use solana_sdk::{
+    account::Account, clock::Slot, clock::UnixTimestamp, commitment_config::CommitmentConfig, pubkey::Pubkey,
+    signature::Signature,
+};
+use solana_transaction_status::{EncodedConfirmedTransactionWithStatusMeta, TransactionStatus, UiConfirmedBlock};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use crate::source::SolanaSource;
+
+/// Token bucket limiting how many RPC requests are made per second. Clones
+/// share the same bucket, so every concurrent fetch draws on one budget.
+///
+/// The bucket holds up to one second's worth of requests, so a burst after
+/// an idle period can briefly exceed the steady rate by that much.
+#[derive(Clone, Debug)]
+pub struct RateLimiter {
+    requests_per_second: f64,
+    bucket: Arc<Mutex<Bucket>>,
+}
+
+#[derive(Debug)]
+struct Bucket {
+    tokens: f64,
+    refilled_at: Instant,
+}
+
+impl RateLimiter {
+    pub fn new(requests_per_second: f64) -> Self {
+        let requests_per_second = requests_per_second.max(f64::MIN_POSITIVE);
+        RateLimiter {
+            requests_per_second,
+            bucket: Arc::new(Mutex::new(Bucket {
+                tokens: requests_per_second,
+                refilled_at: Instant::now(),
+            })),
+        }
+    }
+
+    /// Waits until a single request may be sent.
+    pub async fn acquire(&self) {
+        self.acquire_many(1).await;
+    }
+
+    /// Waits until `requests` requests may be sent, as for a JSON-RPC batch.
+    /// Permits are reserved immediately, so callers are served in the order
+    /// they arrive and a batch larger than the bucket still goes through.
+    pub async fn acquire_many(&self, requests: usize) {
+        let wait = {
+            let mut bucket = self.bucket.lock().unwrap_or_else(|e| e.into_inner());
+            let now = Instant::now();
+            let elapsed = now.duration_since(bucket.refilled_at).as_secs_f64();
+            bucket.tokens = (bucket.tokens + elapsed * self.requests_per_second).min(self.requests_per_second);
+            bucket.refilled_at = now;
+            bucket.tokens -= requests as f64;
+            -bucket.tokens / self.requests_per_second
+        };
+        if wait > 0.0 {
+            tokio::time::sleep(Duration::from_secs_f64(wait)).await;
+        }
+    }
+}
+
+/// A [`SolanaSource`] that takes a permit from `limiter` before every call
+/// to `source`, one per transaction for batched fetches.
+pub struct RateLimitedSource<S> {
+    source: S,
+    limiter: RateLimiter,
+}
+
+impl<S> RateLimitedSource<S> {
+    pub fn new(source: S, limiter: RateLimiter) -> Self {
+        RateLimitedSource { source, limiter }
+    }
+}
+
+#[async_trait]
+impl<S: SolanaSource + Sync> SolanaSource for RateLimitedSource<S> {
+    async fn get_signatures_for_address_with_config(
+        &self,
+        address: &Pubkey,
+        config: GetConfirmedSignaturesForAddress2Config,
+    ) -> ClientResult<Vec<RpcConfirmedTransactionStatusWithSignature>> {
+        self.limiter.acquire().await;
+        self.source.get_signatures_for_address_with_config(address, config).await
+    }
+
+    async fn get_transaction_with_config(
+        &self,
+        signature: &Signature,
+        config: RpcTransactionConfig,
+    ) -> ClientResult<EncodedConfirmedTransactionWithStatusMeta> {
+        self.limiter.acquire().await;
+        self.source.get_transaction_with_config(signature, config).await
+    }
+
+    async fn get_transactions_with_config(
+        &self,
+        signatures: &[Signature],
+        config: RpcTransactionConfig,
+    ) -> Vec<ClientResult<EncodedConfirmedTransactionWithStatusMeta>> {
+        // Providers meter each call inside a batch, not the HTTP request
+        self.limiter.acquire_many(signatures.len()).await;
+        self.source.get_transactions_with_config(signatures, config).await
+    }
+
+    async fn get_signature_statuses_with_history(
+        &self,
+        signatures: &[Signature],
+    ) -> RpcResult<Vec<Option<TransactionStatus>>> {
+        self.limiter.acquire().await;
+        self.source.get_signature_statuses_with_history(signatures).await
+    }
+
+    async fn get_account_with_commitment(
+        &self,
+        pubkey: &Pubkey,
+        commitment: CommitmentConfig,
+    ) -> RpcResult<Option<Account>> {
+        self.limiter.acquire().await;
+        self.source.get_account_with_commitment(pubkey, commitment).await
+    }
+
+    async fn get_slot_with_commitment(&self, commitment: CommitmentConfig) -> ClientResult<Slot> {
+        self.limiter.acquire().await;
+        self.source.get_slot_with_commitment(commitment).await
+    }
+
+    async fn get_block_time(&self, slot: Slot) -> ClientResult<UnixTimestamp> {
+        self.limiter.acquire().await;
+        self.source.get_block_time(slot).await
+    }
+
+    async fn get_block_with_config(&self, slot: Slot, config: RpcBlockConfig) -> ClientResult<UiConfirmedBlock> {
+        self.limiter.acquire().await;
+        self.source.get_block_with_config(slot, config).await
+    }
+
+    fn cache_hits(&self) -> usize {
+        self.source.cache_hits()
+    }
+}
@@ -0,0 +1,268 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use solana_sdk::{commitment_config::CommitmentConfig, pubkey::Pubkey, signature::Signature};
+use std::fs::{self, File};
+use std::io::{BufReader, BufWriter, Write};
+use std::path::Path;
+use std::str::FromStr;
+use tracing::info;
+
+use crate::error::IndexerError;
+use crate::indexer::{
+    fetch_and_process, fetch_signature_page, in_range_signatures, parse_signature, process_transaction, TransferFilter,
+};
+use crate::retry::RetryPolicy;
+use crate::seek::seek_signature_cursor;
+use crate::sink::TransferSink;
+use crate::source::SolanaSource;
+use crate::time::TimeRange;
+
+/// Progress of a checkpointed scan, persisted as JSON so an interrupted run
+/// can pick up where it stopped with [`resume`].
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ScanCheckpoint {
+    pub wallet: String,
+    pub mints: Vec<String>,
+    pub start_time: DateTime<Utc>,
+    pub end_time: DateTime<Utc>,
+    /// Oldest signature fully processed; the scan continues with the
+    /// signatures before it. `None` until the first page is done.
+    pub before: Option<String>,
+    /// Transfers handed to the sink so far, across restarts.
+    pub transfers: usize,
+    pub complete: bool,
+}
+
+impl ScanCheckpoint {
+    pub fn load(path: &Path) -> Result<Self, IndexerError> {
+        Ok(serde_json::from_reader(BufReader::new(File::open(path)?))?)
+    }
+
+    /// Writes to a temporary file first so a crash never leaves a truncated
+    /// checkpoint.
+    pub fn save(&self, path: &Path) -> Result<(), IndexerError> {
+        let tmp = path.with_extension("tmp");
+        let mut writer = BufWriter::new(File::create(&tmp)?);
+        serde_json::to_writer(&mut writer, self)?;
+        writer.flush()?;
+        fs::rename(&tmp, path)?;
+        Ok(())
+    }
+}
+
+/// Indexes like [`crate::indexer::index_token_transfers`], handing transfers
+/// to `sink` page by page and saving a [`ScanCheckpoint`] to
+/// `checkpoint_path` once at least `checkpoint_interval` signatures have
+/// been processed since the last save, and when the scan completes. Returns
+/// the total number of transfers accepted.
+///
+/// Any transaction that can't be fetched, or a sink failure, ends the run
+/// with that error so nothing is skipped; [`resume`] then retries from the
+/// last checkpoint. Transfers accepted after that checkpoint are delivered
+/// again on resume, so sinks should tolerate repeats (keyed by signature).
+///
+/// Cancelling [`TransferFilter::cancellation`] saves the checkpoint as of
+/// the last fully processed page and returns the count so far. So does
+/// reaching [`TransferFilter::max_transfers`], counted across this run's
+/// pages. The transfers of a page cut short are still delivered, but the
+/// checkpoint stays before that page, so they come again on resume and a
+/// resumed run needs a higher limit, or none, to get past it.
+#[allow(clippy::too_many_arguments)]
+pub async fn index_with_checkpoints(
+    client: &(impl SolanaSource + Sync),
+    wallet: &str,
+    mints: &[Pubkey],
+    range: TimeRange,
+    page_limit: usize,
+    concurrency: usize,
+    batch_size: usize,
+    retry: RetryPolicy,
+    filter: &TransferFilter,
+    commitment: CommitmentConfig,
+    checkpoint_path: &Path,
+    checkpoint_interval: usize,
+    sink: &mut (impl TransferSink + Send),
+) -> Result<usize, IndexerError> {
+    let checkpoint = ScanCheckpoint {
+        wallet: wallet.to_string(),
+        mints: mints.iter().map(|mint| mint.to_string()).collect(),
+        start_time: range.start(),
+        end_time: range.end(),
+        before: None,
+        transfers: 0,
+        complete: false,
+    };
+    run_from_checkpoint(
+        client,
+        checkpoint,
+        page_limit,
+        concurrency,
+        batch_size,
+        retry,
+        filter,
+        commitment,
+        checkpoint_path,
+        checkpoint_interval,
+        sink,
+    )
+    .await
+}
+
+/// Continues the scan saved at `checkpoint_path` by
+/// [`index_with_checkpoints`], for the same wallet, mints and time range.
+/// `filter` isn't persisted and should match the original run. A completed
+/// scan returns its transfer count without fetching anything.
+#[allow(clippy::too_many_arguments)]
+pub async fn resume(
+    client: &(impl SolanaSource + Sync),
+    checkpoint_path: &Path,
+    page_limit: usize,
+    concurrency: usize,
+    batch_size: usize,
+    retry: RetryPolicy,
+    filter: &TransferFilter,
+    commitment: CommitmentConfig,
+    checkpoint_interval: usize,
+    sink: &mut (impl TransferSink + Send),
+) -> Result<usize, IndexerError> {
+    let checkpoint = ScanCheckpoint::load(checkpoint_path)?;
+    if checkpoint.complete {
+        return Ok(checkpoint.transfers);
+    }
+    info!("Resuming scan of {} before {:?}", checkpoint.wallet, checkpoint.before);
+    run_from_checkpoint(
+        client,
+        checkpoint,
+        page_limit,
+        concurrency,
+        batch_size,
+        retry,
+        filter,
+        commitment,
+        checkpoint_path,
+        checkpoint_interval,
+        sink,
+    )
+    .await
+}
+
+#[allow(clippy::too_many_arguments)]
+async fn run_from_checkpoint(
+    client: &(impl SolanaSource + Sync),
+    mut checkpoint: ScanCheckpoint,
+    page_limit: usize,
+    concurrency: usize,
+    batch_size: usize,
+    retry: RetryPolicy,
+    filter: &TransferFilter,
+    commitment: CommitmentConfig,
+    checkpoint_path: &Path,
+    checkpoint_interval: usize,
+    sink: &mut (impl TransferSink + Send),
+) -> Result<usize, IndexerError> {
+    let wallet_pubkey = Pubkey::from_str(&checkpoint.wallet)
+        .map_err(|_| IndexerError::InvalidPubkey(checkpoint.wallet.clone()))?;
+    let mints = checkpoint
+        .mints
+        .iter()
+        .map(|mint| Pubkey::from_str(mint).map_err(|_| IndexerError::InvalidPubkey(mint.clone())))
+        .collect::<Result<Vec<_>, _>>()?;
+    let range = TimeRange::new(checkpoint.start_time, checkpoint.end_time)?;
+
+    let mut before: Option<Signature> = match &checkpoint.before {
+        Some(before) => Some(parse_signature(before)?),
+        None => seek_signature_cursor(client, range.end(), &retry, commitment).await,
+    };
+    let mut since_save = 0;
+    let mut page_filter = filter.clone();
+    let mut delivered = 0;
+
+    loop {
+        if filter.is_cancelled() {
+            return stop_cancelled(&checkpoint, checkpoint_path);
+        }
+        let (page, next, _) = fetch_signature_page(
+            client,
+            &wallet_pubkey,
+            before,
+            None,
+            range.start(),
+            page_limit,
+            &retry,
+            filter.listing_commitment(commitment),
+        )
+        .await?;
+        let (in_range, skipped_no_block_time) =
+            in_range_signatures(client, &page, range.start(), range.end(), &retry).await?;
+        filter.check_block_times(skipped_no_block_time)?;
+        page_filter.max_transfers = filter.max_transfers.map(|max| max - delivered);
+        let mut result = fetch_and_process(
+            client,
+            in_range,
+            concurrency,
+            batch_size,
+            retry,
+            commitment,
+            None,
+            &page_filter,
+            |tx, tx_time, signature| process_transaction(tx, &wallet_pubkey, &mints, tx_time, signature, filter),
+        )
+        .await?;
+        if result.stats.cancelled {
+            // The page was only partly processed, so it is redone on resume
+            return stop_cancelled(&checkpoint, checkpoint_path);
+        }
+        if !result.errors.is_empty() {
+            return Err(result.errors.swap_remove(0).1);
+        }
+        if result.stats.truncated {
+            // Older signatures on the page were left unfetched, so the checkpoint stays before it
+            checkpoint.save(checkpoint_path)?;
+            let accepted = checkpoint.transfers + result.transfers.len();
+            for transfer in result.transfers {
+                sink.accept(transfer).await?;
+            }
+            info!("Scan of {} stopped at the transfer limit with {} transfers", checkpoint.wallet, accepted);
+            return Ok(accepted);
+        }
+
+        for transfer in result.transfers {
+            sink.accept(transfer).await?;
+            checkpoint.transfers += 1;
+            delivered += 1;
+        }
+        if let Some(oldest) = page.last() {
+            checkpoint.before = Some(oldest.signature.clone());
+        }
+        since_save += page.len();
+
+        match next {
+            Some(_) if filter.max_transfers.is_some_and(|max| delivered >= max) => {
+                checkpoint.save(checkpoint_path)?;
+                let accepted = checkpoint.transfers;
+                info!("Scan of {} stopped at the transfer limit with {} transfers", checkpoint.wallet, accepted);
+                return Ok(accepted);
+            }
+            Some(cursor) => {
+                before = Some(cursor);
+                if since_save >= checkpoint_interval {
+                    checkpoint.save(checkpoint_path)?;
+                    since_save = 0;
+                }
+            }
+            None => break,
+        }
+    }
+
+    checkpoint.complete = true;
+    checkpoint.save(checkpoint_path)?;
+    info!("Scan of {} complete with {} transfers", checkpoint.wallet, checkpoint.transfers);
+    Ok(checkpoint.transfers)
+}
+
+/// Saves the progress of a cancelled scan so [`resume`] can pick it up.
+fn stop_cancelled(checkpoint: &ScanCheckpoint, checkpoint_path: &Path) -> Result<usize, IndexerError> {
+    checkpoint.save(checkpoint_path)?;
+    info!("Scan of {} cancelled with {} transfers", checkpoint.wallet, checkpoint.transfers);
+    Ok(checkpoint.transfers)
+}
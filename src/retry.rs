@@ -0,0 +1,65 @@
+use rand::Rng;
+use solana_client::client_error::{reqwest::StatusCode, ClientError, ClientErrorKind};
+use std::future::Future;
+use std::time::Duration;
+use tracing::warn;
+
+/// How many times, and how patiently, a failed RPC call is retried.
+#[derive(Clone, Copy, Debug)]
+pub struct RetryPolicy {
+    pub max_retries: u32,
+    pub base_delay: Duration,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        RetryPolicy {
+            max_retries: 5,
+            base_delay: Duration::from_millis(500),
+        }
+    }
+}
+
+/// Runs `op` until it succeeds, fails with a non-transient error, or has
+/// been retried `policy.max_retries` times. The delay before retry `n` is
+/// `base_delay * 2^n` plus up to `base_delay` of random jitter.
+pub async fn retry_with_backoff<F, Fut, T>(policy: &RetryPolicy, mut op: F) -> Result<T, ClientError>
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Result<T, ClientError>>,
+{
+    let mut attempt = 0;
+    loop {
+        match op().await {
+            Ok(value) => return Ok(value),
+            Err(e) if attempt < policy.max_retries && is_transient(&e) => {
+                let backoff = policy.base_delay.saturating_mul(2u32.saturating_pow(attempt));
+                let jitter_ms = rand::thread_rng().gen_range(0..=policy.base_delay.as_millis() as u64);
+                let delay = backoff + Duration::from_millis(jitter_ms);
+                attempt += 1;
+                warn!(
+                    "Transient RPC error (attempt {}/{}), retrying in {:?}: {}",
+                    attempt, policy.max_retries, delay, e
+                );
+                tokio::time::sleep(delay).await;
+            }
+            Err(e) => return Err(e),
+        }
+    }
+}
+
+/// Timeouts, connection failures, 429s and 5xx responses are worth
+/// retrying; deserialization and other client-side errors are not.
+pub(crate) fn is_transient(e: &ClientError) -> bool {
+    match e.kind() {
+        ClientErrorKind::Io(_) => true,
+        ClientErrorKind::Reqwest(e) => {
+            e.is_timeout()
+                || e.is_connect()
+                || e.status().is_some_and(|status| {
+                    status == StatusCode::TOO_MANY_REQUESTS || status.is_server_error()
+                })
+        }
+        _ => false,
+    }
+}
@@ -0,0 +1,149 @@
+use chrono::{DateTime, Utc};
+use solana_client::rpc_config::RpcBlockConfig;
+use solana_sdk::{clock::Slot, commitment_config::CommitmentConfig, signature::Signature};
+use solana_transaction_status::TransactionDetails;
+use std::str::FromStr;
+use tracing::{debug, warn};
+
+use crate::error::IndexerError;
+use crate::retry::{retry_with_backoff, RetryPolicy};
+use crate::source::SolanaSource;
+
+/// Rough slot time used to turn a time difference into a slot distance.
+const ESTIMATED_SLOT_MS: i64 = 400;
+
+/// How far past `end_time` the cursor block may land. A little slack keeps
+/// the number of refinement steps down; the extra signatures are filtered
+/// out by time afterwards.
+const SEEK_TOLERANCE_SECS: i64 = 120;
+
+/// Refinement steps before settling for the closest block found so far.
+const MAX_SEEK_STEPS: usize = 8;
+
+/// Consecutive skipped or missing slots to step over when probing a slot.
+const MAX_SKIPPED_SLOTS: u64 = 16;
+
+/// Finds a `before` cursor for `getSignaturesForAddress` that starts paging
+/// shortly after `end_time` instead of at the chain head.
+///
+/// `before` accepts any signature, not only the wallet's, so this locates a
+/// block just after `end_time` and uses one of its signatures. The block is
+/// found by estimating the slot from the head's block time and refining with
+/// `getBlockTime`, which converges in a handful of steps since slot times are
+/// close to constant. Block times only grow with slot, so the cursor never
+/// skips a signature inside the window.
+///
+/// The cost is fixed at roughly 3 to 10 small RPC calls (`getSlot`, a few
+/// `getBlockTime` and one `getBlock` with signatures only), while paging from
+/// the head costs one full signature page per page of history newer than
+/// `end_time`. A window a year back on a busy wallet can save hundreds of
+/// pages. If `end_time` is within a couple of minutes of the head, no cursor
+/// is needed and `None` is returned after two calls.
+///
+/// Seeking is best effort: any failure is logged and yields `None`, so the
+/// caller falls back to paging from the head.
+pub(crate) async fn seek_signature_cursor(
+    client: &(impl SolanaSource + Sync),
+    end_time: DateTime<Utc>,
+    retry: &RetryPolicy,
+    commitment: CommitmentConfig,
+) -> Option<Signature> {
+    match try_seek(client, end_time, retry, commitment).await {
+        Ok(cursor) => cursor,
+        Err(e) => {
+            warn!("Could not seek to {}, paging from the head instead: {}", end_time, e);
+            None
+        }
+    }
+}
+
+async fn try_seek(
+    client: &(impl SolanaSource + Sync),
+    end_time: DateTime<Utc>,
+    retry: &RetryPolicy,
+    commitment: CommitmentConfig,
+) -> Result<Option<Signature>, IndexerError> {
+    let target = end_time.timestamp();
+    let head = retry_with_backoff(retry, || client.get_slot_with_commitment(commitment)).await?;
+    let Some((mut slot, mut time)) = block_time_before(client, head, retry).await else {
+        return Ok(None);
+    };
+    if time <= target + SEEK_TOLERANCE_SECS {
+        return Ok(None);
+    }
+
+    // Invariant: `slot` is a block with `time` after `target`, so it is always a safe cursor
+    let mut damping = 1;
+    for _ in 0..MAX_SEEK_STEPS {
+        if time <= target + SEEK_TOLERANCE_SECS {
+            break;
+        }
+        // Aim for the middle of the tolerance band so overshooting slightly still lands inside it
+        let aim = target + SEEK_TOLERANCE_SECS / 2;
+        let distance = ((time - aim) * 1000 / ESTIMATED_SLOT_MS / damping).max(1) as u64;
+        let Some((probe, probe_time)) = block_time_after(client, slot.saturating_sub(distance), retry).await
+        else {
+            break;
+        };
+        if probe_time <= target {
+            // Overshot into the window; take a shorter step from the same block
+            damping *= 2;
+        } else if probe < slot {
+            slot = probe;
+            time = probe_time;
+        } else {
+            break;
+        }
+    }
+
+    debug!("Seeking from slot {} (block time {}) for end time {}", slot, time, end_time);
+    Ok(first_block_signature(client, slot, retry, commitment).await)
+}
+
+/// Block time of `slot`, or of the nearest earlier slot that has a block.
+async fn block_time_before(
+    client: &(impl SolanaSource + Sync),
+    slot: Slot,
+    retry: &RetryPolicy,
+) -> Option<(Slot, i64)> {
+    for probe in (slot.saturating_sub(MAX_SKIPPED_SLOTS)..=slot).rev() {
+        if let Ok(time) = retry_with_backoff(retry, || client.get_block_time(probe)).await {
+            return Some((probe, time));
+        }
+    }
+    None
+}
+
+/// Block time of `slot`, or of the nearest later slot that has a block.
+async fn block_time_after(
+    client: &(impl SolanaSource + Sync),
+    slot: Slot,
+    retry: &RetryPolicy,
+) -> Option<(Slot, i64)> {
+    for probe in slot..slot + MAX_SKIPPED_SLOTS {
+        if let Ok(time) = retry_with_backoff(retry, || client.get_block_time(probe)).await {
+            return Some((probe, time));
+        }
+    }
+    None
+}
+
+async fn first_block_signature(
+    client: &(impl SolanaSource + Sync),
+    slot: Slot,
+    retry: &RetryPolicy,
+    commitment: CommitmentConfig,
+) -> Option<Signature> {
+    let config = RpcBlockConfig {
+        transaction_details: Some(TransactionDetails::Signatures),
+        rewards: Some(false),
+        commitment: Some(commitment),
+        max_supported_transaction_version: Some(0),
+        ..RpcBlockConfig::default()
+    };
+    let block = retry_with_backoff(retry, || client.get_block_with_config(slot, config)).await.ok()?;
+    block
+        .signatures?
+        .first()
+        .and_then(|signature| Signature::from_str(signature).ok())
+}
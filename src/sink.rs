@@ -0,0 +1,64 @@
+use async_trait::async_trait;
+use futures::StreamExt;
+use solana_sdk::{commitment_config::CommitmentConfig, pubkey::Pubkey};
+use std::pin::pin;
+
+use crate::error::IndexerError;
+use crate::indexer::{index_token_transfers_stream, TransferFilter};
+use crate::models::Transfer;
+use crate::retry::RetryPolicy;
+use crate::source::SolanaSource;
+use crate::time::TimeRange;
+
+/// Destination for transfers as they are discovered, such as a database
+/// table or a queue.
+#[async_trait]
+pub trait TransferSink {
+    async fn accept(&mut self, transfer: Transfer) -> Result<(), IndexerError>;
+}
+
+/// Collects transfers into a `Vec`, for callers that want the whole set.
+#[derive(Clone, Default)]
+pub struct VecSink {
+    pub transfers: Vec<Transfer>,
+}
+
+#[async_trait]
+impl TransferSink for VecSink {
+    async fn accept(&mut self, transfer: Transfer) -> Result<(), IndexerError> {
+        self.transfers.push(transfer);
+        Ok(())
+    }
+}
+
+/// Hands each transfer to `sink` as soon as it is found, returning how many
+/// were accepted. Built on [`index_token_transfers_stream`], so memory stays
+/// flat and transfers arrive newest signature first; its parameters behave
+/// the same way.
+///
+/// Unlike [`crate::indexer::index_token_transfers`], the first failure,
+/// whether fetching or from the sink, ends the run. Transfers accepted before
+/// it stay with the sink.
+#[allow(clippy::too_many_arguments)]
+pub async fn index_token_transfers_into(
+    client: &(impl SolanaSource + Sync),
+    wallet: &str,
+    mints: &[Pubkey],
+    range: TimeRange,
+    page_limit: usize,
+    retry: RetryPolicy,
+    filter: &TransferFilter,
+    commitment: CommitmentConfig,
+    sink: &mut (impl TransferSink + Send),
+) -> Result<usize, IndexerError> {
+    let mut transfers = pin!(index_token_transfers_stream(
+        client, wallet, mints, range, page_limit, retry, filter, commitment,
+    ));
+
+    let mut accepted = 0;
+    while let Some(transfer) = transfers.next().await {
+        sink.accept(transfer?).await?;
+        accepted += 1;
+    }
+    Ok(accepted)
+}
@@ -0,0 +1,195 @@
+use chrono::{DateTime, Utc};
+use solana_sdk::{
+    commitment_config::CommitmentConfig, native_token::LAMPORTS_PER_SOL, pubkey::Pubkey, signature::Signature,
+};
+use solana_transaction_status::EncodedConfirmedTransactionWithStatusMeta;
+use std::str::FromStr;
+use tracing::{debug, info};
+
+use crate::error::IndexerError;
+use crate::indexer::{fetch_and_process, fetch_signature_pages, in_range_signatures, IndexResult, TransferFilter};
+use crate::instructions::{account_keys, parse_memo};
+use crate::models::{Transfer, TransferType};
+use crate::retry::RetryPolicy;
+use crate::seek::seek_signature_cursor;
+use crate::source::{CountingSource, SolanaSource};
+use crate::time::TimeRange;
+
+/// Mint placeholder recorded on transfers of native SOL, which has no mint.
+pub const NATIVE_SOL_MINT: &str = "SOL";
+
+/// Lamports per SOL as a power of ten, recorded as the decimals of SOL transfers.
+const SOL_DECIMALS: u8 = 9;
+
+/// Indexes native SOL movements for `wallet` from the lamport balances in
+/// each transaction's status meta. When the wallet paid the transaction fee,
+/// the fee is added back so it isn't counted as a transfer (it is still
+/// reported as `fee_lamports`). Other parameters behave as they do for
+/// [`crate::indexer::index_token_transfers`]. The amount range in `filter`
+/// applies to the SOL amount; per-mint minimums don't apply.
+#[allow(clippy::too_many_arguments)]
+pub async fn index_sol_transfers(
+    client: &(impl SolanaSource + Sync),
+    wallet: &str,
+    range: TimeRange,
+    page_limit: usize,
+    concurrency: usize,
+    batch_size: usize,
+    retry: RetryPolicy,
+    filter: &TransferFilter,
+    commitment: CommitmentConfig,
+) -> Result<IndexResult, IndexerError> {
+    let client = &CountingSource::new(client);
+    let hits_before = client.cache_hits();
+    let wallet_pubkey =
+        Pubkey::from_str(wallet).map_err(|_| IndexerError::InvalidPubkey(wallet.to_string()))?;
+
+    info!("Fetching signatures for wallet: {}", wallet);
+    let before = seek_signature_cursor(client, range.end(), &retry, commitment).await;
+    let listing = fetch_signature_pages(
+        client,
+        &wallet_pubkey,
+        range.start(),
+        before,
+        page_limit,
+        filter,
+        &retry,
+        commitment,
+        None,
+    )
+    .await?;
+    filter.check_history(listing.oldest_available, range)?;
+    let signatures = listing.signatures;
+
+    info!("Found {} signatures", signatures.len());
+    let (in_range, skipped_no_block_time) =
+        in_range_signatures(client, &signatures, range.start(), range.end(), &retry).await?;
+    filter.check_transaction_cap(in_range.len())?;
+    filter.check_block_times(skipped_no_block_time)?;
+
+    let mut result = fetch_and_process(
+        client,
+        in_range,
+        concurrency,
+        batch_size,
+        retry,
+        commitment,
+        None,
+        filter,
+        |tx, tx_time, signature| process_sol_transaction(tx, &wallet_pubkey, tx_time, signature, filter),
+    )
+    .await?;
+    result.skipped_no_block_time = skipped_no_block_time;
+    result.stats.signatures_fetched = signatures.len();
+    result.stats.rpc_calls = client.calls();
+    result.stats.cache_hits = client.cache_hits() - hits_before;
+    result.stats.truncated |= listing.truncated;
+    result.stats.oldest_available = listing.oldest_available;
+    result.stats.cancelled |= listing.cancelled;
+
+    info!("Returning {} SOL transfers ({} failed fetches)", result.transfers.len(), result.errors.len());
+    Ok(result)
+}
+
+fn process_sol_transaction(
+    tx: &EncodedConfirmedTransactionWithStatusMeta,
+    wallet_pubkey: &Pubkey,
+    tx_time: DateTime<Utc>,
+    signature: &Signature,
+    filter: &TransferFilter,
+) -> Result<Vec<Transfer>, IndexerError> {
+    let Some(meta) = &tx.transaction.meta else {
+        filter.lenient(signature, "no status meta")?;
+        return Ok(Vec::new());
+    };
+
+    let success = meta.err.is_none();
+    if !success && !filter.include_failed {
+        debug!("Skipping failed transaction {}: {:?}", signature, meta.err);
+        return Ok(Vec::new());
+    }
+
+    // The fee payer is always the first account; its fee isn't a transfer
+    let deltas: Vec<i128> = meta
+        .pre_balances
+        .iter()
+        .zip(meta.post_balances.iter())
+        .enumerate()
+        .map(|(index, (pre, post))| {
+            let delta = *post as i128 - *pre as i128;
+            if index == 0 {
+                delta + meta.fee as i128
+            } else {
+                delta
+            }
+        })
+        .collect();
+
+    let keys = account_keys(tx);
+    let wallet = wallet_pubkey.to_string();
+    let Some(wallet_index) = keys.iter().position(|key| *key == wallet) else {
+        return Ok(Vec::new());
+    };
+    let delta = deltas.get(wallet_index).copied().unwrap_or(0);
+    if delta == 0 {
+        return Ok(Vec::new());
+    }
+
+    let transfer_type = if delta > 0 {
+        TransferType::Received
+    } else {
+        TransferType::Sent
+    };
+    if filter.excludes_direction(&transfer_type) {
+        return Ok(Vec::new());
+    }
+
+    // Largest opposing lamport change, as for token counterparties
+    let counterparty = deltas
+        .iter()
+        .enumerate()
+        .filter(|(index, other)| *index != wallet_index && **other * delta < 0)
+        .max_by_key(|(_, other)| other.unsigned_abs())
+        .and_then(|(index, _)| keys.get(index))
+        .map(|key| key.to_string());
+
+    let amount = delta.unsigned_abs() as f64 / LAMPORTS_PER_SOL as f64;
+    if filter.excludes_amount(NATIVE_SOL_MINT, amount) {
+        return Ok(Vec::new());
+    }
+    let transfer = Transfer {
+        date: tx_time,
+        amount,
+        amount_raw: u64::try_from(delta.unsigned_abs()).unwrap_or(u64::MAX),
+        decimals: SOL_DECIMALS,
+        transfer_type,
+        signature: signature.to_string(),
+        mint: NATIVE_SOL_MINT.to_string(),
+        success,
+        counterparty,
+        counterparty_token_account: None,
+        memo: parse_memo(tx),
+        // Native SOL sits directly in the wallet's system account
+        token_account: wallet.clone(),
+        wallet,
+        fee: None,
+        gross_amount: amount,
+        net_amount: amount,
+        usd_value: None,
+        slot: tx.slot,
+        block_hash: None,
+        swap_group: None,
+        asset: None,
+        kind: None,
+        commitment: None,
+        via_cpi: false,
+        program_id: None,
+        fee_lamports: meta.fee,
+        paid_fee: wallet_index == 0,
+        account_initialized: false,
+    };
+    if filter.excludes_counterparty(&transfer) {
+        return Ok(Vec::new());
+    }
+    Ok(vec![transfer])
+}
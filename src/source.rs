@@ -0,0 +1,366 @@
+use async_trait::async_trait;
+use futures::future;
+use serde_json::{json, Value};
+use solana_client::client_error::{ClientError, ClientErrorKind, Result as ClientResult};
+use solana_client::nonblocking::rpc_client::RpcClient;
+use solana_client::rpc_client::GetConfirmedSignaturesForAddress2Config;
+use solana_client::rpc_config::{RpcBlockConfig, RpcTransactionConfig};
+use solana_client::rpc_request::{RpcError, RpcResponseErrorData};
+use solana_client::rpc_response::{RpcConfirmedTransactionStatusWithSignature, RpcResult};
+use solana_sdk::{
+    account::Account, clock::Slot, clock::UnixTimestamp, commitment_config::CommitmentConfig, pubkey::Pubkey,
+    signature::Signature,
+};
+use solana_transaction_status::{EncodedConfirmedTransactionWithStatusMeta, TransactionStatus, UiConfirmedBlock};
+use std::io;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use crate::retry::is_transient;
+
+/// The RPC calls the indexer makes, so a fake returning canned transactions
+/// can stand in for a live node. Implemented for the nonblocking
+/// [`RpcClient`] by forwarding to its methods of the same name. Its timeout
+/// and headers can't be read back for a batch request, so it fetches
+/// transactions one by one; [`crate::client::HttpSource`] batches them.
+#[async_trait]
+pub trait SolanaSource {
+    async fn get_signatures_for_address_with_config(
+        &self,
+        address: &Pubkey,
+        config: GetConfirmedSignaturesForAddress2Config,
+    ) -> ClientResult<Vec<RpcConfirmedTransactionStatusWithSignature>>;
+
+    async fn get_transaction_with_config(
+        &self,
+        signature: &Signature,
+        config: RpcTransactionConfig,
+    ) -> ClientResult<EncodedConfirmedTransactionWithStatusMeta>;
+
+    /// Fetches several transactions at once, returning one result per
+    /// signature in the same order. Defaults to issuing the individual
+    /// requests concurrently; sources that can do better (such as a JSON-RPC
+    /// batch) should override it.
+    async fn get_transactions_with_config(
+        &self,
+        signatures: &[Signature],
+        config: RpcTransactionConfig,
+    ) -> Vec<ClientResult<EncodedConfirmedTransactionWithStatusMeta>>
+    where
+        Self: Sync,
+    {
+        future::join_all(
+            signatures
+                .iter()
+                .map(|signature| self.get_transaction_with_config(signature, config)),
+        )
+        .await
+    }
+
+    async fn get_signature_statuses_with_history(
+        &self,
+        signatures: &[Signature],
+    ) -> RpcResult<Vec<Option<TransactionStatus>>>;
+
+    async fn get_account_with_commitment(
+        &self,
+        pubkey: &Pubkey,
+        commitment: CommitmentConfig,
+    ) -> RpcResult<Option<Account>>;
+
+    async fn get_slot_with_commitment(&self, commitment: CommitmentConfig) -> ClientResult<Slot>;
+
+    async fn get_block_time(&self, slot: Slot) -> ClientResult<UnixTimestamp>;
+
+    async fn get_block_with_config(&self, slot: Slot, config: RpcBlockConfig) -> ClientResult<UiConfirmedBlock>;
+
+    /// Transactions answered from a cache rather than fetched, since the
+    /// source was created. Zero unless a [`crate::cache::CachedSource`] is
+    /// involved; wrappers report the count of the source they wrap.
+    fn cache_hits(&self) -> usize {
+        0
+    }
+}
+
+#[async_trait]
+impl SolanaSource for RpcClient {
+    async fn get_signatures_for_address_with_config(
+        &self,
+        address: &Pubkey,
+        config: GetConfirmedSignaturesForAddress2Config,
+    ) -> ClientResult<Vec<RpcConfirmedTransactionStatusWithSignature>> {
+        RpcClient::get_signatures_for_address_with_config(self, address, config).await
+    }
+
+    async fn get_transaction_with_config(
+        &self,
+        signature: &Signature,
+        config: RpcTransactionConfig,
+    ) -> ClientResult<EncodedConfirmedTransactionWithStatusMeta> {
+        RpcClient::get_transaction_with_config(self, signature, config).await
+    }
+
+    async fn get_signature_statuses_with_history(
+        &self,
+        signatures: &[Signature],
+    ) -> RpcResult<Vec<Option<TransactionStatus>>> {
+        RpcClient::get_signature_statuses_with_history(self, signatures).await
+    }
+
+    async fn get_account_with_commitment(
+        &self,
+        pubkey: &Pubkey,
+        commitment: CommitmentConfig,
+    ) -> RpcResult<Option<Account>> {
+        RpcClient::get_account_with_commitment(self, pubkey, commitment).await
+    }
+
+    async fn get_slot_with_commitment(&self, commitment: CommitmentConfig) -> ClientResult<Slot> {
+        RpcClient::get_slot_with_commitment(self, commitment).await
+    }
+
+    async fn get_block_time(&self, slot: Slot) -> ClientResult<UnixTimestamp> {
+        RpcClient::get_block_time(self, slot).await
+    }
+
+    async fn get_block_with_config(&self, slot: Slot, config: RpcBlockConfig) -> ClientResult<UiConfirmedBlock> {
+        RpcClient::get_block_with_config(self, slot, config).await
+    }
+}
+
+/// Lets wrappers such as [`crate::cache::CachedSource`] borrow a source
+/// instead of owning it.
+#[async_trait]
+impl<T: SolanaSource + Sync + ?Sized> SolanaSource for &T {
+    async fn get_signatures_for_address_with_config(
+        &self,
+        address: &Pubkey,
+        config: GetConfirmedSignaturesForAddress2Config,
+    ) -> ClientResult<Vec<RpcConfirmedTransactionStatusWithSignature>> {
+        (**self).get_signatures_for_address_with_config(address, config).await
+    }
+
+    async fn get_transaction_with_config(
+        &self,
+        signature: &Signature,
+        config: RpcTransactionConfig,
+    ) -> ClientResult<EncodedConfirmedTransactionWithStatusMeta> {
+        (**self).get_transaction_with_config(signature, config).await
+    }
+
+    async fn get_transactions_with_config(
+        &self,
+        signatures: &[Signature],
+        config: RpcTransactionConfig,
+    ) -> Vec<ClientResult<EncodedConfirmedTransactionWithStatusMeta>> {
+        (**self).get_transactions_with_config(signatures, config).await
+    }
+
+    async fn get_signature_statuses_with_history(
+        &self,
+        signatures: &[Signature],
+    ) -> RpcResult<Vec<Option<TransactionStatus>>> {
+        (**self).get_signature_statuses_with_history(signatures).await
+    }
+
+    async fn get_account_with_commitment(
+        &self,
+        pubkey: &Pubkey,
+        commitment: CommitmentConfig,
+    ) -> RpcResult<Option<Account>> {
+        (**self).get_account_with_commitment(pubkey, commitment).await
+    }
+
+    async fn get_slot_with_commitment(&self, commitment: CommitmentConfig) -> ClientResult<Slot> {
+        (**self).get_slot_with_commitment(commitment).await
+    }
+
+    async fn get_block_time(&self, slot: Slot) -> ClientResult<UnixTimestamp> {
+        (**self).get_block_time(slot).await
+    }
+
+    async fn get_block_with_config(&self, slot: Slot, config: RpcBlockConfig) -> ClientResult<UiConfirmedBlock> {
+        (**self).get_block_with_config(slot, config).await
+    }
+
+    fn cache_hits(&self) -> usize {
+        (**self).cache_hits()
+    }
+}
+
+/// Counts the requests made through `source`, a batch counting once, for
+/// [`crate::indexer::IndexStats::rpc_calls`].
+pub(crate) struct CountingSource<S> {
+    source: S,
+    calls: AtomicUsize,
+}
+
+impl<S> CountingSource<S> {
+    pub(crate) fn new(source: S) -> Self {
+        CountingSource {
+            source,
+            calls: AtomicUsize::new(0),
+        }
+    }
+
+    pub(crate) fn calls(&self) -> usize {
+        self.calls.load(Ordering::Relaxed)
+    }
+
+    fn count(&self) {
+        self.calls.fetch_add(1, Ordering::Relaxed);
+    }
+}
+
+#[async_trait]
+impl<S: SolanaSource + Sync> SolanaSource for CountingSource<S> {
+    async fn get_signatures_for_address_with_config(
+        &self,
+        address: &Pubkey,
+        config: GetConfirmedSignaturesForAddress2Config,
+    ) -> ClientResult<Vec<RpcConfirmedTransactionStatusWithSignature>> {
+        self.count();
+        self.source.get_signatures_for_address_with_config(address, config).await
+    }
+
+    async fn get_transaction_with_config(
+        &self,
+        signature: &Signature,
+        config: RpcTransactionConfig,
+    ) -> ClientResult<EncodedConfirmedTransactionWithStatusMeta> {
+        self.count();
+        self.source.get_transaction_with_config(signature, config).await
+    }
+
+    async fn get_transactions_with_config(
+        &self,
+        signatures: &[Signature],
+        config: RpcTransactionConfig,
+    ) -> Vec<ClientResult<EncodedConfirmedTransactionWithStatusMeta>> {
+        self.count();
+        self.source.get_transactions_with_config(signatures, config).await
+    }
+
+    async fn get_signature_statuses_with_history(
+        &self,
+        signatures: &[Signature],
+    ) -> RpcResult<Vec<Option<TransactionStatus>>> {
+        self.count();
+        self.source.get_signature_statuses_with_history(signatures).await
+    }
+
+    async fn get_account_with_commitment(
+        &self,
+        pubkey: &Pubkey,
+        commitment: CommitmentConfig,
+    ) -> RpcResult<Option<Account>> {
+        self.count();
+        self.source.get_account_with_commitment(pubkey, commitment).await
+    }
+
+    async fn get_slot_with_commitment(&self, commitment: CommitmentConfig) -> ClientResult<Slot> {
+        self.count();
+        self.source.get_slot_with_commitment(commitment).await
+    }
+
+    async fn get_block_time(&self, slot: Slot) -> ClientResult<UnixTimestamp> {
+        self.count();
+        self.source.get_block_time(slot).await
+    }
+
+    async fn get_block_with_config(&self, slot: Slot, config: RpcBlockConfig) -> ClientResult<UiConfirmedBlock> {
+        self.count();
+        self.source.get_block_with_config(slot, config).await
+    }
+
+    fn cache_hits(&self) -> usize {
+        self.source.cache_hits()
+    }
+}
+
+/// Posts one `getTransaction` request per signature as a JSON-RPC batch and
+/// matches the replies back up by id.
+#[allow(clippy::result_large_err)]
+pub(crate) async fn send_transaction_batch(
+    http: &reqwest::Client,
+    url: &str,
+    signatures: &[Signature],
+    config: RpcTransactionConfig,
+) -> ClientResult<Vec<ClientResult<EncodedConfirmedTransactionWithStatusMeta>>> {
+    let requests: Vec<Value> = signatures
+        .iter()
+        .enumerate()
+        .map(|(id, signature)| {
+            json!({
+                "jsonrpc": "2.0",
+                "id": id,
+                "method": "getTransaction",
+                "params": [signature.to_string(), config],
+            })
+        })
+        .collect();
+
+    let response = http
+        .post(url)
+        .header(reqwest::header::CONTENT_TYPE, "application/json")
+        .body(Value::Array(requests).to_string())
+        .send()
+        .await?
+        .error_for_status()?;
+    // An endpoint without batch support answers with a single error object, which fails here
+    let replies: Vec<Value> = serde_json::from_slice(&response.bytes().await?)?;
+
+    let mut results: Vec<_> = signatures
+        .iter()
+        .map(|signature| Err(ClientErrorKind::Custom(format!("No batch reply for {}", signature)).into()))
+        .collect();
+    for reply in replies {
+        let Some(id) = reply["id"].as_u64().and_then(|id| usize::try_from(id).ok()) else {
+            continue;
+        };
+        if let Some(slot) = results.get_mut(id) {
+            *slot = parse_batch_reply(reply, &signatures[id]);
+        }
+    }
+    Ok(results)
+}
+
+/// One copy of a failed batch request's error per signature. `ClientError`
+/// can't be cloned, so each copy carries the message, as an I/O error when
+/// the failure was transient so retries and [`crate::pool::ClientPool`]
+/// failover still apply.
+#[allow(clippy::result_large_err)]
+pub(crate) fn batch_failure(
+    e: &ClientError,
+    count: usize,
+) -> Vec<ClientResult<EncodedConfirmedTransactionWithStatusMeta>> {
+    let message = format!("Batch request failed: {}", e);
+    let transient = is_transient(e);
+    (0..count)
+        .map(|_| {
+            let kind = if transient {
+                ClientErrorKind::Io(io::Error::other(message.clone()))
+            } else {
+                ClientErrorKind::Custom(message.clone())
+            };
+            Err(kind.into())
+        })
+        .collect()
+}
+
+#[allow(clippy::result_large_err)]
+fn parse_batch_reply(
+    mut reply: Value,
+    signature: &Signature,
+) -> ClientResult<EncodedConfirmedTransactionWithStatusMeta> {
+    if let Some(error) = reply.get("error") {
+        return Err(ClientError::from(RpcError::RpcResponseError {
+            code: error["code"].as_i64().unwrap_or_default(),
+            message: error["message"].as_str().unwrap_or_default().to_string(),
+            data: RpcResponseErrorData::Empty,
+        }));
+    }
+    match reply["result"].take() {
+        Value::Null => Err(ClientErrorKind::Custom(format!("Transaction {} not found", signature)).into()),
+        result => Ok(serde_json::from_value(result)?),
+    }
+}
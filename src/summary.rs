@@ -0,0 +1,247 @@
+use chrono::{DateTime, Datelike, Duration, NaiveDate, Utc};
+use serde::Serialize;
+use std::collections::BTreeMap;
+
+use crate::export::round_amount;
+use crate::models::{Transfer, TransferExt, TransferType};
+
+/// Period length used to bucket transfers in [`summarize_by`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Granularity {
+    Day,
+    /// ISO 8601 weeks, Monday to Sunday, keyed by their Monday. Use
+    /// [`NaiveDate::iso_week`] on the key for the ISO year and week number,
+    /// which differ from the calendar year around New Year.
+    Week,
+    Month,
+    /// Fiscal years starting on the first of `start_month` (1 to 12; out of
+    /// range values are clamped), keyed by that first day.
+    FiscalYear { start_month: u32 },
+}
+
+impl Granularity {
+    /// First day of the period containing `date`, in UTC. Weeks start on
+    /// Monday.
+    pub fn period_start(&self, date: DateTime<Utc>) -> NaiveDate {
+        let day = date.date_naive();
+        match self {
+            Granularity::Day => day,
+            Granularity::Week => {
+                day - Duration::days(day.weekday().num_days_from_monday() as i64)
+            }
+            Granularity::Month => day.with_day(1).unwrap_or(day),
+            Granularity::FiscalYear { start_month } => {
+                let start_month = (*start_month).clamp(1, 12);
+                // The first of the start month already belongs to the new fiscal year
+                let year = if day.month() >= start_month { day.year() } else { day.year() - 1 };
+                NaiveDate::from_ymd_opt(year, start_month, 1).unwrap_or(day)
+            }
+        }
+    }
+}
+
+/// Totals and counts over a set of transfers. Internal and unknown transfers
+/// are counted but don't contribute to the received, sent or net totals.
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct TransferSummary {
+    pub total_received: f64,
+    pub total_sent: f64,
+    pub net_flow: f64,
+    pub received_count: usize,
+    pub sent_count: usize,
+    pub internal_count: usize,
+    pub unknown_count: usize,
+    pub earliest: Option<DateTime<Utc>>,
+    pub latest: Option<DateTime<Utc>>,
+}
+
+pub fn summarize(transfers: &[Transfer]) -> TransferSummary {
+    let mut summary = TransferSummary::default();
+    for t in transfers {
+        summary.add(t);
+    }
+    summary
+}
+
+/// Summarizes transfers per period, keyed by the first day of each period.
+pub fn summarize_by(transfers: &[Transfer], group_by: Granularity) -> BTreeMap<NaiveDate, TransferSummary> {
+    let mut periods: BTreeMap<NaiveDate, TransferSummary> = BTreeMap::new();
+    for t in transfers {
+        periods.entry(group_by.period_start(t.date)).or_default().add(t);
+    }
+    periods
+}
+
+/// Summarizes transfers per logical asset: the transfer's `asset` label, or
+/// its mint when it has none.
+pub fn summarize_by_asset(transfers: &[Transfer]) -> BTreeMap<String, TransferSummary> {
+    let mut assets: BTreeMap<String, TransferSummary> = BTreeMap::new();
+    for t in transfers {
+        let asset = t.asset.as_ref().unwrap_or(&t.mint);
+        assets.entry(asset.clone()).or_default().add(t);
+    }
+    assets
+}
+
+/// Periodic report built by [`generate_report`], serializable for JSON
+/// output or templates.
+#[derive(Clone, Debug, Serialize)]
+pub struct Report {
+    pub periods: Vec<PeriodReport>,
+    /// Totals over every period.
+    pub overall: TransferSummary,
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct PeriodReport {
+    /// First day of the period, as keyed by [`Granularity::period_start`].
+    pub period_start: NaiveDate,
+    /// Transfers before the period.
+    pub opening_count: usize,
+    /// Transfers up to the end of the period, its own included.
+    pub closing_count: usize,
+    pub summary: TransferSummary,
+    /// Counterparties with the largest received plus sent volume in the
+    /// period, largest first.
+    pub top_counterparties: Vec<CounterpartyVolume>,
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct CounterpartyVolume {
+    pub counterparty: String,
+    pub volume: f64,
+    pub count: usize,
+}
+
+/// Summarizes transfers per period like [`summarize_by`], adding running
+/// transfer counts and the `top_n` counterparties by volume in each period.
+/// Periods without transfers are left out. Transfers without a counterparty
+/// don't rank, and volumes are summed as is, so pass a single mint's
+/// transfers.
+pub fn generate_report(transfers: &[Transfer], period: Granularity, top_n: usize) -> Report {
+    let mut grouped: BTreeMap<NaiveDate, Vec<&Transfer>> = BTreeMap::new();
+    for t in transfers {
+        grouped.entry(period.period_start(t.date)).or_default().push(t);
+    }
+
+    let mut opening_count = 0;
+    let periods = grouped
+        .into_iter()
+        .map(|(period_start, transfers)| {
+            let mut summary = TransferSummary::default();
+            let mut volumes: BTreeMap<&str, CounterpartyVolume> = BTreeMap::new();
+            for t in &transfers {
+                summary.add(t);
+                let Some(counterparty) = t.counterparty.as_deref() else {
+                    continue;
+                };
+                if !matches!(t.transfer_type, TransferType::Received | TransferType::Sent) {
+                    continue;
+                }
+                let entry = volumes.entry(counterparty).or_insert_with(|| CounterpartyVolume {
+                    counterparty: counterparty.to_string(),
+                    volume: 0.0,
+                    count: 0,
+                });
+                entry.volume += t.amount;
+                entry.count += 1;
+            }
+            let mut top_counterparties: Vec<CounterpartyVolume> = volumes.into_values().collect();
+            // Keyed by address first, so ties rank the same way every run
+            top_counterparties.sort_by(|a, b| b.volume.total_cmp(&a.volume));
+            top_counterparties.truncate(top_n);
+
+            let report = PeriodReport {
+                period_start,
+                opening_count,
+                closing_count: opening_count + transfers.len(),
+                summary,
+                top_counterparties,
+            };
+            opening_count = report.closing_count;
+            report
+        })
+        .collect();
+
+    Report { periods, overall: summarize(transfers) }
+}
+
+impl TransferSummary {
+    /// Copy with the totals rounded half to even to `decimals` places, for
+    /// display. Counts and dates are unchanged.
+    pub fn rounded(&self, decimals: u8) -> TransferSummary {
+        TransferSummary {
+            total_received: round_amount(self.total_received, decimals),
+            total_sent: round_amount(self.total_sent, decimals),
+            net_flow: round_amount(self.net_flow, decimals),
+            ..self.clone()
+        }
+    }
+
+    fn add(&mut self, t: &Transfer) {
+        match t.transfer_type {
+            TransferType::Received => {
+                self.total_received += t.amount;
+                self.received_count += 1;
+            }
+            TransferType::Sent => {
+                self.total_sent += t.amount;
+                self.sent_count += 1;
+            }
+            TransferType::Internal => self.internal_count += 1,
+            TransferType::Unknown => self.unknown_count += 1,
+        }
+        self.net_flow = self.total_received - self.total_sent;
+        self.earliest = Some(self.earliest.map_or(t.date, |earliest| earliest.min(t.date)));
+        self.latest = Some(self.latest.map_or(t.date, |latest| latest.max(t.date)));
+    }
+}
+
+/// Balance after each transfer, starting from `starting`, in date order
+/// (ties broken by signature). Received transfers add their amount and sent
+/// transfers subtract it; internal transfers move funds between tracked
+/// wallets and leave the balance unchanged, but still get a point, as do
+/// unknown ones.
+pub fn running_balance(transfers: &[Transfer], starting: f64) -> Vec<(DateTime<Utc>, f64)> {
+    let mut ordered: Vec<&Transfer> = transfers.iter().collect();
+    ordered.sort_by(|a, b| a.date.cmp(&b.date).then_with(|| a.signature.cmp(&b.signature)));
+
+    let mut balance = starting;
+    ordered
+        .into_iter()
+        .map(|t| {
+            match t.transfer_type {
+                TransferType::Received => balance += t.amount,
+                TransferType::Sent => balance -= t.amount,
+                TransferType::Internal | TransferType::Unknown => {}
+            }
+            (t.date, balance)
+        })
+        .collect()
+}
+
+/// Outcome of [`reconcile`].
+#[derive(Clone, Debug, Serialize)]
+pub struct ReconcileResult {
+    /// Starting balance plus received minus sent.
+    pub expected_end: f64,
+    pub actual_end: f64,
+    /// `actual_end - expected_end`. Positive means transfers in were missed
+    /// (or transfers out double counted), negative the reverse.
+    pub discrepancy: f64,
+    /// Whether the discrepancy is within half the smallest unit of the
+    /// transfers' mint, i.e. no more than floating point noise.
+    pub balanced: bool,
+}
+
+/// Checks that `transfers` account for the change from `start_balance` to
+/// `end_balance`, as a self-check against missed or double-counted
+/// transfers. Internal and unknown transfers don't count, as in
+/// [`TransferSummary`], so pass the transfers of a single mint and wallet.
+pub fn reconcile(start_balance: f64, end_balance: f64, transfers: &[Transfer]) -> ReconcileResult {
+    let expected_end = start_balance + transfers.net();
+    let discrepancy = end_balance - expected_end;
+    let decimals = transfers.iter().map(|t| t.decimals).max().unwrap_or(9);
+    let epsilon = 0.5 / 10f64.powi(decimals as i32);
+    ReconcileResult { expected_end, actual_end: end_balance, discrepancy, balanced: discrepancy.abs() <= epsilon }
+}
@@ -0,0 +1,147 @@
+use chrono::{DateTime, Duration, NaiveDate, NaiveDateTime, NaiveTime, TimeZone, Utc};
+use std::fmt;
+
+use crate::error::IndexerError;
+
+/// Source of the current time for the relative range helpers and
+/// [`crate::indexer::TransferFilter::clock`], so tests can pin it instead of
+/// depending on the system clock.
+pub trait Clock: fmt::Debug + Send + Sync {
+    fn now(&self) -> DateTime<Utc>;
+}
+
+/// The system clock, used by helpers that don't take a [`Clock`].
+#[derive(Clone, Copy, Debug, Default)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> DateTime<Utc> {
+        Utc::now()
+    }
+}
+
+/// A clock stopped at a fixed time.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct FixedClock(pub DateTime<Utc>);
+
+impl Clock for FixedClock {
+    fn now(&self) -> DateTime<Utc> {
+        self.0
+    }
+}
+
+/// An inclusive span of time to index, guaranteed to start no later than it
+/// ends.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct TimeRange {
+    start: DateTime<Utc>,
+    end: DateTime<Utc>,
+}
+
+impl TimeRange {
+    /// Fails with [`IndexerError::InvalidTimeRange`] if `start` is after `end`.
+    pub fn new(start: DateTime<Utc>, end: DateTime<Utc>) -> Result<Self, IndexerError> {
+        if start > end {
+            return Err(IndexerError::InvalidTimeRange(start, end));
+        }
+        Ok(TimeRange { start, end })
+    }
+
+    /// The `days` days up to now.
+    pub fn last_days(days: u32) -> Self {
+        TimeRange::last_days_with_clock(days, &SystemClock)
+    }
+
+    /// The `days` days up to `clock`'s current time.
+    pub fn last_days_with_clock(days: u32, clock: &impl Clock) -> Self {
+        let end = clock.now();
+        TimeRange {
+            start: end - Duration::days(i64::from(days)),
+            end,
+        }
+    }
+
+    /// The calendar month `month` (1 to 12) of `year` in UTC, through its
+    /// last millisecond. Fails with [`IndexerError::InvalidDateTime`] for a
+    /// month out of range.
+    pub fn month(year: i32, month: u32) -> Result<Self, IndexerError> {
+        let invalid = || IndexerError::InvalidDateTime(format!("{}-{:02}", year, month));
+        let first = NaiveDate::from_ymd_opt(year, month, 1).ok_or_else(invalid)?;
+        let next = if month == 12 {
+            NaiveDate::from_ymd_opt(year + 1, 1, 1)
+        } else {
+            NaiveDate::from_ymd_opt(year, month + 1, 1)
+        }
+        .ok_or_else(invalid)?;
+
+        let start = first.and_time(NaiveTime::MIN).and_utc();
+        let end = next.and_time(NaiveTime::MIN).and_utc() - Duration::milliseconds(1);
+        Ok(TimeRange { start, end })
+    }
+
+    pub fn start(&self) -> DateTime<Utc> {
+        self.start
+    }
+
+    pub fn end(&self) -> DateTime<Utc> {
+        self.end
+    }
+
+    /// Whether `time` falls within the range, both ends included.
+    pub fn contains(&self, time: DateTime<Utc>) -> bool {
+        self.start <= time && time <= self.end
+    }
+}
+
+/// Local datetime layouts accepted by [`parse_time_range`], besides RFC 3339
+/// and bare dates.
+const DATETIME_FORMATS: [&str; 4] = [
+    "%Y-%m-%d %H:%M:%S",
+    "%Y-%m-%dT%H:%M:%S",
+    "%Y-%m-%d %H:%M",
+    "%Y-%m-%dT%H:%M",
+];
+
+/// Parses a start/end pair of user-supplied times into a [`TimeRange`].
+///
+/// Each value may be an RFC 3339 timestamp (its own offset wins), a local
+/// datetime such as `2024-03-01 09:30` or a bare date, which are interpreted
+/// in `tz`. A bare start date means the start of that day and a bare end
+/// date the end of it, so `("2024-03-01", "2024-03-01")` covers the whole
+/// day. Fails if either value can't be parsed, falls in a DST gap, or the
+/// range ends before it starts.
+pub fn parse_time_range<Tz: TimeZone>(start: &str, end: &str, tz: &Tz) -> Result<TimeRange, IndexerError> {
+    let start_time = parse_time(start, tz, NaiveTime::MIN)?;
+    let end_time = parse_time(end, tz, end_of_day())?;
+    TimeRange::new(start_time, end_time)
+}
+
+fn parse_time<Tz: TimeZone>(
+    value: &str,
+    tz: &Tz,
+    date_only_time: NaiveTime,
+) -> Result<DateTime<Utc>, IndexerError> {
+    let value = value.trim();
+    if let Ok(time) = DateTime::parse_from_rfc3339(value) {
+        return Ok(time.with_timezone(&Utc));
+    }
+
+    let naive = DATETIME_FORMATS
+        .iter()
+        .find_map(|format| NaiveDateTime::parse_from_str(value, format).ok())
+        .or_else(|| {
+            NaiveDate::parse_from_str(value, "%Y-%m-%d")
+                .ok()
+                .map(|date| date.and_time(date_only_time))
+        })
+        .ok_or_else(|| IndexerError::InvalidDateTime(value.to_string()))?;
+
+    tz.from_local_datetime(&naive)
+        .earliest()
+        .map(|time| time.with_timezone(&Utc))
+        .ok_or_else(|| IndexerError::InvalidDateTime(value.to_string()))
+}
+
+fn end_of_day() -> NaiveTime {
+    NaiveTime::from_hms_milli_opt(23, 59, 59, 999).expect("valid time")
+}
@@ -1,12 +1,8 @@
 use actix_web::{web, HttpResponse, Responder};
-use serde::Serialize;
+use log::error;
 
-use crate::models::{Transfer, TransferType};
-
-#[derive(Serialize)]
-struct TransferResponse {
-    transfers: Vec<Transfer>,
-}
+use solana_usdc_indexer::export::export_csv;
+use solana_usdc_indexer::models::{Transfer, TransferType};
 
 pub async fn get_transfers(transfers: web::Data<Vec<Transfer>>) -> impl Responder {
     let table_content = if transfers.is_empty() {
@@ -18,6 +14,8 @@ pub async fn get_transfers(transfers: web::Data<Vec<Transfer>>) -> impl Responde
                 let type_str = match t.transfer_type {
                     TransferType::Sent => "Sent".to_string(),
                     TransferType::Received => "Received".to_string(),
+                    TransferType::Internal => "Internal".to_string(),
+                    TransferType::Unknown => "Unknown".to_string(),
                 };
                 format!(
                     "<tr><td>{}</td><td>{:.6}</td><td>{}</td><td><a href=\"https://explorer.solana.com/tx/{}\">{}</a></td></tr>",
@@ -57,4 +55,15 @@ pub async fn get_transfers(transfers: web::Data<Vec<Transfer>>) -> impl Responde
         table_content
     );
     HttpResponse::Ok().content_type("text/html").body(html)
+}
+
+pub async fn get_transfers_csv(transfers: web::Data<Vec<Transfer>>) -> impl Responder {
+    let mut body = Vec::new();
+    match export_csv(&transfers, &mut body) {
+        Ok(()) => HttpResponse::Ok().content_type("text/csv").body(body),
+        Err(e) => {
+            error!("Failed to export transfers as CSV: {}", e);
+            HttpResponse::InternalServerError().finish()
+        }
+    }
 }
\ No newline at end of file
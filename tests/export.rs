@@ -0,0 +1,9 @@
+//! QIF output stays well-formed whatever account name it is given.
+
+use solana_usdc_indexer::export::export_qif;
+
+#[test]
+fn qif_account_name_cannot_break_the_header() {
+    let qif = export_qif(&[], "^!Treasury\r\n!Type:Invst\n^");
+    assert_eq!(qif, "!Account\nNTreasury  !Type:Invst ^\nTBank\n^\n!Type:Bank\n");
+}
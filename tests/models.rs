@@ -0,0 +1,76 @@
+//! The [`TransferExt`] helpers over a hand-built mix of directions.
+
+use chrono::{TimeZone, Utc};
+
+use solana_usdc_indexer::models::{Transfer, TransferExt, TransferType};
+
+fn transfer(transfer_type: TransferType, amount: f64) -> Transfer {
+    Transfer {
+        date: Utc.timestamp_opt(1_700_000_000, 0).single().expect("valid timestamp"),
+        amount,
+        amount_raw: (amount * 1e6) as u64,
+        decimals: 6,
+        signature: format!("{:?}-{}", transfer_type, amount),
+        transfer_type,
+        mint: "EPjFWdd5AufqSSqeM2qN1xzybapC8G4wEGGkZwyTDt1v".to_string(),
+        success: true,
+        counterparty: None,
+        counterparty_token_account: None,
+        memo: None,
+        wallet: "GfsJWjmGXMfct8JMR9Lm9ySUnniZbnGUTQDbT8ipWf9U".to_string(),
+        token_account: "8kFLcoBFb9LGzjCicnJpDhGas2FoPL7gX9NBDs6FGQdG".to_string(),
+        fee: None,
+        gross_amount: amount,
+        net_amount: amount,
+        usd_value: None,
+        slot: 250_000_000,
+        block_hash: None,
+        swap_group: None,
+        asset: None,
+        kind: None,
+        commitment: None,
+        via_cpi: false,
+        program_id: None,
+        fee_lamports: 5000,
+        paid_fee: false,
+        account_initialized: false,
+    }
+}
+
+fn mixed() -> Vec<Transfer> {
+    vec![
+        transfer(TransferType::Received, 25.0),
+        transfer(TransferType::Sent, 10.0),
+        transfer(TransferType::Internal, 100.0),
+        transfer(TransferType::Received, 5.5),
+        transfer(TransferType::Unknown, 1000.0),
+        transfer(TransferType::Sent, 2.25),
+    ]
+}
+
+#[test]
+fn received_and_sent_keep_only_their_direction() {
+    let transfers = mixed();
+    let received: Vec<f64> = transfers.received().map(|t| t.amount).collect();
+    let sent: Vec<f64> = transfers.sent().map(|t| t.amount).collect();
+    assert_eq!(received, vec![25.0, 5.5]);
+    assert_eq!(sent, vec![10.0, 2.25]);
+}
+
+#[test]
+fn totals_exclude_internal_and_unknown() {
+    let transfers = mixed();
+    assert_eq!(transfers.total_received(), 30.5);
+    assert_eq!(transfers.total_sent(), 12.25);
+    assert_eq!(transfers.net(), 18.25);
+}
+
+#[test]
+fn empty_slice_totals_to_zero() {
+    let transfers: &[Transfer] = &[];
+    assert_eq!(transfers.received().count(), 0);
+    assert_eq!(transfers.sent().count(), 0);
+    assert_eq!(transfers.total_received(), 0.0);
+    assert_eq!(transfers.total_sent(), 0.0);
+    assert_eq!(transfers.net(), 0.0);
+}
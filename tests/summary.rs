@@ -0,0 +1,25 @@
+//! Period bucketing by [`Granularity`] around calendar boundaries.
+
+use chrono::{Datelike, NaiveDate, TimeZone, Utc};
+
+use solana_usdc_indexer::summary::Granularity;
+
+#[test]
+fn week_follows_iso_weeks_across_new_year() {
+    // Tuesday 2024-12-31 and Wednesday 2025-01-01 are both in ISO week 2025-W01
+    let new_years_eve = Utc.with_ymd_and_hms(2024, 12, 31, 12, 0, 0).unwrap();
+    let new_years_day = Utc.with_ymd_and_hms(2025, 1, 1, 12, 0, 0).unwrap();
+    let monday = NaiveDate::from_ymd_opt(2024, 12, 30).unwrap();
+    assert_eq!(Granularity::Week.period_start(new_years_eve), monday);
+    assert_eq!(Granularity::Week.period_start(new_years_day), monday);
+    assert_eq!((monday.iso_week().year(), monday.iso_week().week()), (2025, 1));
+}
+
+#[test]
+fn fiscal_year_starts_on_the_first_of_its_month() {
+    let fiscal = Granularity::FiscalYear { start_month: 7 };
+    let last_day = Utc.with_ymd_and_hms(2024, 6, 30, 23, 59, 59).unwrap();
+    let first_day = Utc.with_ymd_and_hms(2024, 7, 1, 0, 0, 0).unwrap();
+    assert_eq!(fiscal.period_start(last_day), NaiveDate::from_ymd_opt(2023, 7, 1).unwrap());
+    assert_eq!(fiscal.period_start(first_day), NaiveDate::from_ymd_opt(2024, 7, 1).unwrap());
+}
@@ -0,0 +1,19 @@
+use chrono::{Duration, TimeZone, Utc};
+
+use solana_usdc_indexer::time::{FixedClock, TimeRange};
+
+#[test]
+fn last_days_ends_at_the_clock() {
+    let now = Utc.with_ymd_and_hms(2024, 3, 15, 12, 0, 0).unwrap();
+    let range = TimeRange::last_days_with_clock(4, &FixedClock(now));
+    assert_eq!(range.start(), now - Duration::days(4));
+    assert_eq!(range.end(), now);
+}
+
+#[test]
+fn last_zero_days_is_an_instant() {
+    let now = Utc.with_ymd_and_hms(2024, 1, 1, 0, 0, 0).unwrap();
+    let range = TimeRange::last_days_with_clock(0, &FixedClock(now));
+    assert!(range.contains(now));
+    assert!(!range.contains(now + Duration::milliseconds(1)));
+}
@@ -0,0 +1,910 @@
+//! Transfer extraction over recorded transactions in `tests/fixtures`, served
+//! by a fake [`SolanaSource`] so no node is needed.
+
+use async_trait::async_trait;
+use chrono::{DateTime, TimeZone, Utc};
+use solana_client::client_error::{ClientError, ClientErrorKind, Result as ClientResult};
+use solana_client::rpc_client::GetConfirmedSignaturesForAddress2Config;
+use solana_client::rpc_config::{RpcBlockConfig, RpcTransactionConfig};
+use solana_client::rpc_response::{RpcConfirmedTransactionStatusWithSignature, RpcResult};
+use solana_sdk::{
+    account::Account,
+    clock::{Slot, UnixTimestamp},
+    commitment_config::{CommitmentConfig, CommitmentLevel},
+    pubkey,
+    pubkey::Pubkey,
+    signature::Signature,
+};
+use solana_transaction_status::{
+    EncodedConfirmedTransactionWithStatusMeta, TransactionStatus, UiConfirmedBlock, UiTransactionEncoding,
+};
+use tokio_util::sync::CancellationToken;
+use std::collections::HashMap;
+use std::fs;
+use std::str::FromStr;
+use std::sync::{Arc, Mutex};
+
+use solana_usdc_indexer::cache::{CachedSource, FileTransactionCache};
+use solana_usdc_indexer::client::RpcClientConfig;
+use solana_usdc_indexer::error::IndexerError;
+use solana_usdc_indexer::indexer::{
+    fingerprint, index_token_transfers, index_token_transfers_multi, process_signatures, CommitmentFallback,
+    IndexResult, ParseMode, TransferFilter,
+};
+use solana_usdc_indexer::instructions::{account_key, account_keys};
+use solana_usdc_indexer::models::{BalanceChangeKind, Transfer, TransferType};
+use solana_usdc_indexer::pool::ClientPool;
+use solana_usdc_indexer::resume::{index_with_checkpoints, resume, ScanCheckpoint};
+use solana_usdc_indexer::retry::RetryPolicy;
+use solana_usdc_indexer::sol::index_sol_transfers;
+use solana_usdc_indexer::sink::VecSink;
+use solana_usdc_indexer::source::SolanaSource;
+use solana_usdc_indexer::time::{FixedClock, TimeRange};
+
+const WALLET: &str = "GfsJWjmGXMfct8JMR9Lm9ySUnniZbnGUTQDbT8ipWf9U";
+const WALLET_USDC: &str = "8kFLcoBFb9LGzjCicnJpDhGas2FoPL7gX9NBDs6FGQdG";
+const WALLET_USDC_2: &str = "2CJNAD56t2RqvmKRkX64ESpLtoNnCEh9SzEazWUvhZGC";
+const WALLET_X: &str = "2dU9L5bJc8P2pZmRv521iDZ3EwYMvomjdht9fmqfvvgk";
+const WALLET_WSOL: &str = "EAESMKugFUFYa3qqxMbtG4Zv172DVhEYkp9EuB4JvJUg";
+const OTHER: &str = "FciD4i2WPEYinnKaCzFZAPTUsRxTCpJM6FyQmezmkkoj";
+const OTHER_USDC: &str = "AHa7HTEGsQGKxBfpVrzj18fscaYMUUcibw8oVpWfwd8t";
+const POOL: &str = "3gLESRnfLgzAqu6PwGhBwsiBsnQ7BAtyWHhZ5zNcDPMF";
+const POOL_USDC: &str = "4YZT6Sv9uKTmmWE7XyTYfm61mJbNN4a9jr1h3R9RY79V";
+const POOL_X: &str = "6SAhbAMBMmYVdFTo47n9ots5rzh8Jt74DNoh6aq3BNZV";
+/// The wallet's associated USDC account, the only way to attribute balances
+/// recorded without an owner.
+const WALLET_USDC_ATA: &str = "53bjdfCnUSTrctVKjTnZf8FqriCRRuGYbWd7m56DAEAa";
+const TOKEN_PROGRAM: &str = "TokenkegQfeZyiNwAJbNbGKPFXCWuBvf9Ss623VQ5DA";
+const SWAP_PROGRAM: &str = "7gU15D4EdPNSWPeixCoCHBj4moN7U8PgK2oSAvqTSCwE";
+
+const FEE_MINT: Pubkey = pubkey!("8PctRPgaenZuStf9HbuonQECALQmic8N1r1kdLFQNRvt");
+const USDC: Pubkey = pubkey!("EPjFWdd5AufqSSqeM2qN1xzybapC8G4wEGGkZwyTDt1v");
+const WSOL: Pubkey = pubkey!("So11111111111111111111111111111111111111112");
+const OTHER_MINT: Pubkey = pubkey!("63XUGjq1d7dTiXrRbf7QjrT3VhnijeVJJujkbtwKT9o8");
+
+/// A recorded transaction and what a signature listing says about it.
+struct Fixture {
+    signature: Signature,
+    slot: Slot,
+    block_time: UnixTimestamp,
+    json: String,
+}
+
+/// Answers `getSignaturesForAddress` and `getTransaction` from a set of
+/// fixtures and fails everything else, so a test notices if extraction
+/// starts making other calls. An address's listing holds, newest first,
+/// every fixture that mentions it anywhere.
+struct FixtureSource {
+    fixtures: Vec<Fixture>,
+    /// Start each page at the `before` cursor itself, as some providers do.
+    repeat_cursor: bool,
+    /// List signatures without their block times, as for some old slots.
+    undated: bool,
+    /// Answer `finalized` lookups with not found, as for transactions that
+    /// haven't reached finality yet.
+    unfinalized: bool,
+    fetches: Mutex<HashMap<Signature, usize>>,
+}
+
+impl FixtureSource {
+    fn load(names: &[&str]) -> Self {
+        let mut fixtures: Vec<Fixture> = names
+            .iter()
+            .map(|name| {
+                let path = format!("{}/tests/fixtures/{}.json", env!("CARGO_MANIFEST_DIR"), name);
+                let json = fs::read_to_string(path).expect("fixture exists");
+                let value: serde_json::Value = serde_json::from_str(&json).expect("fixture is JSON");
+                let signature = value["transaction"]["signatures"][0].as_str().expect("fixture has a signature");
+                Fixture {
+                    signature: Signature::from_str(signature).expect("valid signature"),
+                    slot: value["slot"].as_u64().expect("fixture has a slot"),
+                    block_time: value["blockTime"].as_i64().expect("fixture has a block time"),
+                    json,
+                }
+            })
+            .collect();
+        fixtures.sort_by_key(|fixture| std::cmp::Reverse(fixture.slot));
+        FixtureSource {
+            fixtures,
+            repeat_cursor: false,
+            undated: false,
+            unfinalized: false,
+            fetches: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Signature of the only fixture loaded.
+    fn signature(&self) -> Signature {
+        assert_eq!(self.fixtures.len(), 1, "one fixture loaded");
+        self.fixtures[0].signature
+    }
+
+    /// How many times each signature's transaction was fetched.
+    fn fetches(&self) -> HashMap<Signature, usize> {
+        self.fetches.lock().unwrap().clone()
+    }
+}
+
+fn unexpected(call: &str) -> ClientError {
+    ClientErrorKind::Custom(format!("unexpected {} call", call)).into()
+}
+
+#[async_trait]
+impl SolanaSource for FixtureSource {
+    async fn get_signatures_for_address_with_config(
+        &self,
+        address: &Pubkey,
+        config: GetConfirmedSignaturesForAddress2Config,
+    ) -> ClientResult<Vec<RpcConfirmedTransactionStatusWithSignature>> {
+        let listed: Vec<&Fixture> =
+            self.fixtures.iter().filter(|fixture| fixture.json.contains(&address.to_string())).collect();
+        let start = match config.before {
+            Some(before) => match listed.iter().position(|fixture| fixture.signature == before) {
+                Some(index) if self.repeat_cursor => index,
+                Some(index) => index + 1,
+                None => return Err(unexpected("getSignaturesForAddress with an unknown cursor")),
+            },
+            None => 0,
+        };
+        Ok(listed[start..]
+            .iter()
+            .take_while(|fixture| Some(fixture.signature) != config.until)
+            .take(config.limit.unwrap_or(1000))
+            .map(|fixture| RpcConfirmedTransactionStatusWithSignature {
+                signature: fixture.signature.to_string(),
+                slot: fixture.slot,
+                err: None,
+                memo: None,
+                block_time: (!self.undated).then_some(fixture.block_time),
+                confirmation_status: None,
+            })
+            .collect())
+    }
+
+    async fn get_transaction_with_config(
+        &self,
+        signature: &Signature,
+        config: RpcTransactionConfig,
+    ) -> ClientResult<EncodedConfirmedTransactionWithStatusMeta> {
+        let fixture = self.fixtures.iter().find(|fixture| fixture.signature == *signature);
+        let finalized = config.commitment == Some(CommitmentConfig::finalized());
+        let Some(fixture) = fixture.filter(|_| !(self.unfinalized && finalized)) else {
+            return Err(ClientErrorKind::Custom(format!("transaction {} not found", signature)).into());
+        };
+        *self.fetches.lock().unwrap().entry(*signature).or_default() += 1;
+        Ok(serde_json::from_str(&fixture.json)?)
+    }
+
+    async fn get_signature_statuses_with_history(
+        &self,
+        _signatures: &[Signature],
+    ) -> RpcResult<Vec<Option<TransactionStatus>>> {
+        Err(unexpected("getSignatureStatuses"))
+    }
+
+    async fn get_account_with_commitment(
+        &self,
+        _pubkey: &Pubkey,
+        _commitment: CommitmentConfig,
+    ) -> RpcResult<Option<Account>> {
+        Err(unexpected("getAccountInfo"))
+    }
+
+    async fn get_slot_with_commitment(&self, _commitment: CommitmentConfig) -> ClientResult<Slot> {
+        Err(unexpected("getSlot"))
+    }
+
+    async fn get_block_time(&self, _slot: Slot) -> ClientResult<UnixTimestamp> {
+        Err(unexpected("getBlockTime"))
+    }
+
+    async fn get_block_with_config(&self, _slot: Slot, _config: RpcBlockConfig) -> ClientResult<UiConfirmedBlock> {
+        Err(unexpected("getBlock"))
+    }
+}
+
+fn time(seconds: i64) -> DateTime<Utc> {
+    Utc.timestamp_opt(seconds, 0).single().expect("valid timestamp")
+}
+
+/// The span every fixture's block time falls in.
+fn fixture_range() -> TimeRange {
+    TimeRange::new(time(1_700_000_000), time(1_700_001_000)).expect("valid range")
+}
+
+/// Extracts the transfers of a single fixture, skipping signature listing.
+async fn index(fixture: &str, mints: &[Pubkey], filter: &TransferFilter) -> (Signature, Vec<Transfer>) {
+    index_wallet(WALLET, fixture, mints, filter).await
+}
+
+/// Like [`index`], for the side of `wallet`.
+async fn index_wallet(
+    wallet: &str,
+    fixture: &str,
+    mints: &[Pubkey],
+    filter: &TransferFilter,
+) -> (Signature, Vec<Transfer>) {
+    let source = FixtureSource::load(&[fixture]);
+    let signature = source.signature();
+    let result = process_signatures(
+        &source,
+        wallet,
+        mints,
+        &[signature],
+        fixture_range(),
+        1,
+        1,
+        RetryPolicy::default(),
+        filter,
+        CommitmentConfig::confirmed(),
+    )
+    .await
+    .expect("indexing succeeds");
+    assert!(result.errors.is_empty(), "fetch errors: {:?}", result.errors);
+    (signature, result.transfers)
+}
+
+/// A USDC transfer with the fields every fixture shares; tests override the
+/// rest.
+fn usdc_transfer(signature: &Signature, seconds: i64, slot: u64) -> Transfer {
+    Transfer {
+        date: time(seconds),
+        amount: 0.0,
+        amount_raw: 0,
+        decimals: 6,
+        transfer_type: TransferType::Received,
+        signature: signature.to_string(),
+        mint: USDC.to_string(),
+        success: true,
+        counterparty: None,
+        counterparty_token_account: None,
+        memo: None,
+        wallet: WALLET.to_string(),
+        token_account: WALLET_USDC.to_string(),
+        fee: None,
+        gross_amount: 0.0,
+        net_amount: 0.0,
+        usd_value: None,
+        slot,
+        block_hash: None,
+        swap_group: None,
+        asset: None,
+        kind: Some(BalanceChangeKind::TransferChecked),
+        commitment: Some(CommitmentLevel::Confirmed),
+        via_cpi: false,
+        program_id: None,
+        fee_lamports: 5000,
+        paid_fee: false,
+        account_initialized: false,
+    }
+}
+
+#[tokio::test]
+async fn simple_receive() {
+    let (signature, transfers) = index("receive", &[USDC], &TransferFilter::default()).await;
+    assert_eq!(
+        transfers,
+        vec![Transfer {
+            amount: 25.0,
+            gross_amount: 25.0,
+            net_amount: 25.0,
+            amount_raw: 25_000_000,
+            counterparty: Some(OTHER.to_string()),
+            counterparty_token_account: Some(OTHER_USDC.to_string()),
+            ..usdc_transfer(&signature, 1_700_000_100, 250_000_100)
+        }]
+    );
+}
+
+#[tokio::test]
+async fn simple_send() {
+    let (signature, transfers) = index("send", &[USDC], &TransferFilter::default()).await;
+    assert_eq!(
+        transfers,
+        vec![Transfer {
+            amount: 10.0,
+            gross_amount: 10.0,
+            net_amount: 10.0,
+            amount_raw: 10_000_000,
+            transfer_type: TransferType::Sent,
+            counterparty: Some(OTHER.to_string()),
+            counterparty_token_account: Some(OTHER_USDC.to_string()),
+            paid_fee: true,
+            ..usdc_transfer(&signature, 1_700_000_200, 250_000_200)
+        }]
+    );
+}
+
+#[tokio::test]
+async fn failed_transaction_is_skipped() {
+    let (_, transfers) = index("failed", &[USDC], &TransferFilter::default()).await;
+    assert_eq!(transfers, Vec::new());
+}
+
+#[tokio::test]
+async fn failed_transaction_moves_nothing_when_included() {
+    let filter = TransferFilter { include_failed: true, ..TransferFilter::default() };
+    let (_, transfers) = index("failed", &[USDC], &filter).await;
+    assert_eq!(transfers, Vec::new());
+}
+
+#[tokio::test]
+async fn swap_legs_share_a_group() {
+    let (signature, transfers) = index("swap", &[USDC, OTHER_MINT], &TransferFilter::default()).await;
+    let base = usdc_transfer(&signature, 1_700_000_400, 250_000_400);
+    assert_eq!(
+        transfers,
+        vec![
+            Transfer {
+                amount: 10.0,
+                gross_amount: 10.0,
+                net_amount: 10.0,
+                amount_raw: 10_000_000,
+                transfer_type: TransferType::Sent,
+                counterparty: Some(POOL.to_string()),
+                counterparty_token_account: Some(POOL_USDC.to_string()),
+                swap_group: Some(signature.to_string()),
+                via_cpi: true,
+                program_id: Some(SWAP_PROGRAM.to_string()),
+                paid_fee: true,
+                ..base.clone()
+            },
+            Transfer {
+                amount: 3.0,
+                gross_amount: 3.0,
+                net_amount: 3.0,
+                amount_raw: 3_000_000_000,
+                decimals: 9,
+                mint: OTHER_MINT.to_string(),
+                token_account: WALLET_X.to_string(),
+                counterparty: Some(POOL.to_string()),
+                counterparty_token_account: Some(POOL_X.to_string()),
+                swap_group: Some(signature.to_string()),
+                kind: Some(BalanceChangeKind::Transfer),
+                via_cpi: true,
+                program_id: Some(SWAP_PROGRAM.to_string()),
+                paid_fee: true,
+                ..base
+            },
+        ]
+    );
+}
+
+#[tokio::test]
+async fn wsol_wrap_is_suppressed() {
+    let (_, transfers) = index("wsol_wrap", &[USDC, WSOL], &TransferFilter::default()).await;
+    assert_eq!(transfers, Vec::new());
+}
+
+#[tokio::test]
+async fn wsol_wrap_is_kept_when_tracked() {
+    let filter = TransferFilter { track_wsol_wrapping: true, ..TransferFilter::default() };
+    let (signature, transfers) = index("wsol_wrap", &[USDC, WSOL], &filter).await;
+    assert_eq!(
+        transfers,
+        vec![Transfer {
+            amount: 1.0,
+            gross_amount: 1.0,
+            net_amount: 1.0,
+            amount_raw: 1_000_000_000,
+            decimals: 9,
+            mint: WSOL.to_string(),
+            token_account: WALLET_WSOL.to_string(),
+            kind: None,
+            paid_fee: true,
+            ..usdc_transfer(&signature, 1_700_000_500, 250_000_500)
+        }]
+    );
+}
+
+#[tokio::test]
+async fn closed_account_drain_is_sent() {
+    let (signature, transfers) = index("closed_drain", &[USDC], &TransferFilter::default()).await;
+    assert_eq!(
+        transfers,
+        vec![Transfer {
+            amount: 40.0,
+            gross_amount: 40.0,
+            net_amount: 40.0,
+            amount_raw: 40_000_000,
+            transfer_type: TransferType::Sent,
+            counterparty: Some(OTHER.to_string()),
+            counterparty_token_account: Some(OTHER_USDC.to_string()),
+            paid_fee: true,
+            ..usdc_transfer(&signature, 1_700_000_600, 250_000_600)
+        }]
+    );
+}
+
+#[tokio::test]
+async fn closed_account_drain_is_sent_in_instruction_mode() {
+    let (_, from_balances) = index("closed_drain", &[USDC], &TransferFilter::default()).await;
+    let filter = TransferFilter { parse_mode: ParseMode::Instructions, ..TransferFilter::default() };
+    let (_, from_instructions) = index("closed_drain", &[USDC], &filter).await;
+    assert_eq!(from_instructions, from_balances);
+}
+
+#[tokio::test]
+async fn fingerprint_ignores_order_and_duplicates() {
+    let (_, received) = index("receive", &[USDC], &TransferFilter::default()).await;
+    let (_, sent) = index("send", &[USDC], &TransferFilter::default()).await;
+    let forward = [received.clone(), sent.clone()].concat();
+    let backward = [sent.clone(), received.clone(), sent.clone()].concat();
+    assert_eq!(fingerprint(&forward), fingerprint(&backward));
+    assert_ne!(fingerprint(&forward), fingerprint(&received));
+
+    let mut changed = forward.clone();
+    changed[0].memo = Some("refund".to_string());
+    assert_ne!(fingerprint(&forward), fingerprint(&changed));
+}
+
+#[tokio::test]
+async fn wallet_history_is_listed_and_extracted() {
+    let source = FixtureSource::load(&["receive", "send"]);
+    let result = index_token_transfers(
+        &source,
+        WALLET,
+        &[USDC],
+        fixture_range(),
+        1000,
+        1,
+        1,
+        RetryPolicy::default(),
+        &TransferFilter::default(),
+        CommitmentConfig::confirmed(),
+        None,
+    )
+    .await
+    .expect("indexing succeeds");
+    assert!(result.errors.is_empty(), "fetch errors: {:?}", result.errors);
+
+    let received = &source.fixtures[1].signature;
+    let sent = &source.fixtures[0].signature;
+    let summary: Vec<_> = result
+        .transfers
+        .iter()
+        .map(|t| (t.signature.clone(), t.transfer_type.clone(), t.amount_raw))
+        .collect();
+    assert_eq!(
+        summary,
+        vec![
+            (received.to_string(), TransferType::Received, 25_000_000),
+            (sent.to_string(), TransferType::Sent, 10_000_000),
+        ]
+    );
+    assert_eq!(result.stats.signatures_fetched, 2);
+    assert_eq!(result.stats.transactions_fetched, 2);
+    assert_eq!(source.fetches(), HashMap::from([(*received, 1), (*sent, 1)]));
+}
+
+#[tokio::test]
+async fn ownerless_balance_is_attributed_by_ata() {
+    let (signature, transfers) = index("ownerless_receive", &[USDC], &TransferFilter::default()).await;
+    assert_eq!(
+        transfers,
+        vec![Transfer {
+            amount: 25.0,
+            gross_amount: 25.0,
+            net_amount: 25.0,
+            amount_raw: 25_000_000,
+            token_account: WALLET_USDC_ATA.to_string(),
+            kind: Some(BalanceChangeKind::Transfer),
+            ..usdc_transfer(&signature, 1_700_000_700, 250_000_700)
+        }]
+    );
+}
+
+#[tokio::test]
+async fn ownerless_balance_of_another_account_is_unknown_when_included() {
+    let filter = TransferFilter { include_unknown: true, ..TransferFilter::default() };
+    let (signature, transfers) = index("ownerless_receive", &[USDC], &filter).await;
+    let base = Transfer {
+        amount: 25.0,
+        gross_amount: 25.0,
+        net_amount: 25.0,
+        amount_raw: 25_000_000,
+        kind: Some(BalanceChangeKind::Transfer),
+        ..usdc_transfer(&signature, 1_700_000_700, 250_000_700)
+    };
+    assert_eq!(
+        transfers,
+        vec![
+            Transfer { token_account: WALLET_USDC_ATA.to_string(), ..base.clone() },
+            Transfer { transfer_type: TransferType::Unknown, token_account: OTHER_USDC.to_string(), ..base },
+        ]
+    );
+}
+
+#[tokio::test]
+async fn ownerless_balance_of_another_account_fails_strict_runs() {
+    let source = FixtureSource::load(&["ownerless_receive"]);
+    let filter = TransferFilter { strict: true, ..TransferFilter::default() };
+    let result = process_signatures(
+        &source,
+        WALLET,
+        &[USDC],
+        &[source.signature()],
+        fixture_range(),
+        1,
+        1,
+        RetryPolicy::default(),
+        &filter,
+        CommitmentConfig::confirmed(),
+    )
+    .await;
+    assert!(matches!(result, Err(IndexerError::StrictViolation(_))), "got {:?}", result.map(|r| r.transfers));
+}
+
+#[tokio::test]
+async fn repeated_page_cursors_are_processed_once() {
+    let source = FixtureSource { repeat_cursor: true, ..FixtureSource::load(&["receive", "send", "closed_drain"]) };
+    let result = index_token_transfers(
+        &source,
+        WALLET,
+        &[USDC],
+        fixture_range(),
+        2,
+        1,
+        1,
+        RetryPolicy::default(),
+        &TransferFilter::default(),
+        CommitmentConfig::confirmed(),
+        None,
+    )
+    .await
+    .expect("indexing succeeds");
+
+    let signatures: Vec<Signature> = source.fixtures.iter().map(|fixture| fixture.signature).collect();
+    assert_eq!(result.stats.signatures_fetched, 3);
+    assert_eq!(source.fetches(), signatures.iter().map(|signature| (*signature, 1)).collect());
+    let mut indexed: Vec<String> = result.transfers.iter().map(|t| t.signature.clone()).collect();
+    indexed.sort();
+    let mut expected: Vec<String> = signatures.iter().map(Signature::to_string).collect();
+    expected.sort();
+    assert_eq!(indexed, expected);
+}
+
+#[tokio::test]
+async fn lookup_table_addresses_follow_static_keys() {
+    let source = FixtureSource::load(&["v0_lookup"]);
+    let tx: EncodedConfirmedTransactionWithStatusMeta =
+        serde_json::from_str(&source.fixtures[0].json).expect("fixture decodes");
+    // Static keys, then the loaded writable and readonly addresses
+    let usdc = USDC.to_string();
+    assert_eq!(account_keys(&tx), vec![OTHER, OTHER_USDC, TOKEN_PROGRAM, WALLET_USDC, usdc.as_str()]);
+    assert_eq!(account_key(&tx, 3), Some(WALLET_USDC));
+    assert_eq!(account_key(&tx, 4), Some(usdc.as_str()));
+
+    let (signature, transfers) = index("v0_lookup", &[USDC], &TransferFilter::default()).await;
+    assert_eq!(
+        transfers,
+        vec![Transfer {
+            amount: 25.0,
+            gross_amount: 25.0,
+            net_amount: 25.0,
+            amount_raw: 25_000_000,
+            counterparty: Some(OTHER.to_string()),
+            counterparty_token_account: Some(OTHER_USDC.to_string()),
+            // Compiled instructions aren't decoded, so the kind of change is unknown
+            kind: None,
+            ..usdc_transfer(&signature, 1_700_000_800, 250_000_800)
+        }]
+    );
+}
+
+#[tokio::test]
+async fn transfer_fee_separates_gross_and_net() {
+    for parse_mode in [ParseMode::BalanceDeltas, ParseMode::Instructions] {
+        let filter = TransferFilter { parse_mode, ..TransferFilter::default() };
+        let (_, sent) = index_wallet(WALLET, "fee_transfer", &[FEE_MINT], &filter).await;
+        let (_, received) = index_wallet(OTHER, "fee_transfer", &[FEE_MINT], &filter).await;
+        let legs: Vec<_> = sent
+            .iter()
+            .chain(&received)
+            .map(|t| (t.transfer_type.clone(), t.amount, t.gross_amount, t.net_amount, t.fee))
+            .collect();
+        assert_eq!(
+            legs,
+            vec![
+                (TransferType::Sent, 10.0, 10.0, 9.75, Some(0.25)),
+                (TransferType::Received, 9.75, 10.0, 9.75, Some(0.25)),
+            ],
+            "{:?}",
+            filter.parse_mode
+        );
+        for t in sent.iter().chain(&received) {
+            assert_eq!(Some(t.gross_amount - t.net_amount), t.fee, "{:?} {:?}", filter.parse_mode, t.transfer_type);
+        }
+    }
+}
+
+async fn index_multi(fixtures: &[&str], wallets: &[&str], mints: &[Pubkey]) -> Vec<Transfer> {
+    let source = FixtureSource::load(fixtures);
+    let result = index_token_transfers_multi(
+        &source,
+        wallets,
+        mints,
+        fixture_range(),
+        1000,
+        1,
+        1,
+        RetryPolicy::default(),
+        &TransferFilter::default(),
+        CommitmentConfig::confirmed(),
+    )
+    .await
+    .expect("indexing succeeds");
+    assert!(result.errors.is_empty(), "fetch errors: {:?}", result.errors);
+    result.transfers
+}
+
+#[tokio::test]
+async fn multi_wallet_keeps_equal_legs_into_separate_accounts() {
+    let transfers = index_multi(&["split_receive"], &[WALLET, WALLET], &[USDC]).await;
+    let legs: Vec<_> = transfers.iter().map(|t| (t.token_account.as_str(), t.amount_raw)).collect();
+    assert_eq!(legs, vec![(WALLET_USDC, 5_000_000), (WALLET_USDC_2, 5_000_000)]);
+}
+
+#[tokio::test]
+async fn multi_wallet_pairs_fee_transfer_between_tracked_wallets() {
+    let transfers = index_multi(&["fee_transfer"], &[WALLET, OTHER], &[FEE_MINT]).await;
+    let legs: Vec<_> = transfers
+        .iter()
+        .map(|t| (t.wallet.as_str(), t.transfer_type.clone(), t.gross_amount, t.net_amount))
+        .collect();
+    assert_eq!(legs, vec![(WALLET, TransferType::Internal, 10.0, 9.75)]);
+}
+
+async fn index_sol(fixtures: &[&str], filter: &TransferFilter) -> Result<IndexResult, IndexerError> {
+    let source = FixtureSource::load(fixtures);
+    index_sol_transfers(
+        &source,
+        WALLET,
+        fixture_range(),
+        1000,
+        1,
+        1,
+        RetryPolicy::default(),
+        filter,
+        CommitmentConfig::confirmed(),
+    )
+    .await
+}
+
+#[tokio::test]
+async fn sol_amount_range_applies() {
+    let result = index_sol(&["sol_send"], &TransferFilter::default()).await.expect("indexing succeeds");
+    let sent: Vec<_> = result.transfers.iter().map(|t| (t.transfer_type.clone(), t.amount)).collect();
+    assert_eq!(sent, vec![(TransferType::Sent, 2.0)]);
+
+    for amount_filter in [(Some(2.5), None), (None, Some(1.5))] {
+        let filter = TransferFilter { amount_filter, ..TransferFilter::default() };
+        let result = index_sol(&["sol_send"], &filter).await.expect("indexing succeeds");
+        assert_eq!(result.transfers, Vec::new(), "{:?}", amount_filter);
+    }
+    let filter = TransferFilter { amount_filter: (Some(2.0), Some(2.0)), ..TransferFilter::default() };
+    let result = index_sol(&["sol_send"], &filter).await.expect("indexing succeeds");
+    assert_eq!(result.transfers.len(), 1);
+}
+
+#[tokio::test]
+async fn sol_undated_signatures_fail_strict_runs() {
+    let source = FixtureSource { undated: true, ..FixtureSource::load(&["sol_send"]) };
+    let filter = TransferFilter { strict: true, ..TransferFilter::default() };
+    let result = index_sol_transfers(
+        &source,
+        WALLET,
+        fixture_range(),
+        1000,
+        1,
+        1,
+        RetryPolicy::default(),
+        &filter,
+        CommitmentConfig::confirmed(),
+    )
+    .await;
+    assert!(matches!(result, Err(IndexerError::StrictViolation(_))), "got {:?}", result.map(|r| r.transfers));
+}
+
+#[tokio::test]
+async fn sol_page_cap_truncates() {
+    let filter = TransferFilter { max_pages: Some(1), ..TransferFilter::default() };
+    let source = FixtureSource::load(&["sol_send", "sol_receive"]);
+    let result = index_sol_transfers(
+        &source,
+        WALLET,
+        fixture_range(),
+        1,
+        1,
+        1,
+        RetryPolicy::default(),
+        &filter,
+        CommitmentConfig::confirmed(),
+    )
+    .await
+    .expect("indexing succeeds");
+    let newest: Vec<_> = result.transfers.iter().map(|t| (t.transfer_type.clone(), t.amount)).collect();
+    assert_eq!(newest, vec![(TransferType::Received, 1.0)]);
+    assert_eq!(result.stats.signatures_fetched, 1);
+    assert!(result.stats.truncated);
+}
+
+#[tokio::test]
+async fn sol_history_ending_inside_the_range_is_reported() {
+    // One signature per page, so the listing ends on an empty page after full ones
+    let index = |filter: TransferFilter| async move {
+        let source = FixtureSource::load(&["sol_send", "sol_receive"]);
+        index_sol_transfers(
+            &source,
+            WALLET,
+            fixture_range(),
+            1,
+            1,
+            1,
+            RetryPolicy::default(),
+            &filter,
+            CommitmentConfig::confirmed(),
+        )
+        .await
+    };
+    let oldest = time(1_700_000_950);
+    let result = index(TransferFilter::default()).await.expect("indexing succeeds");
+    assert_eq!(result.stats.oldest_available, Some(oldest));
+    assert_eq!(result.transfers.len(), 2);
+
+    let strict = index(TransferFilter { strict: true, ..TransferFilter::default() }).await;
+    assert!(
+        matches!(strict, Err(IndexerError::HistoryUnavailable { oldest_available }) if oldest_available == oldest),
+        "got {:?}",
+        strict.map(|r| r.transfers)
+    );
+}
+
+#[tokio::test]
+async fn sol_listing_stops_when_cancelled() {
+    let token = CancellationToken::new();
+    token.cancel();
+    let filter = TransferFilter { cancellation: Some(token), ..TransferFilter::default() };
+    let result = index_sol(&["sol_send", "sol_receive"], &filter).await.expect("indexing succeeds");
+    assert_eq!(result.stats.signatures_fetched, 0);
+    assert!(result.stats.cancelled);
+    assert_eq!(result.transfers, Vec::new());
+}
+
+#[tokio::test]
+async fn cache_keeps_commitments_apart() {
+    let dir = std::env::temp_dir().join(format!("transfers-cache-{}", std::process::id()));
+    let fixtures = FixtureSource::load(&["receive"]);
+    let signature = fixtures.signature();
+    let source = CachedSource::new(&fixtures, FileTransactionCache::new(&dir).expect("cache dir"));
+    let config = |commitment| RpcTransactionConfig {
+        encoding: Some(UiTransactionEncoding::JsonParsed),
+        commitment: Some(commitment),
+        max_supported_transaction_version: Some(0),
+    };
+
+    // A copy fetched at `confirmed` must not answer a `finalized` lookup
+    for commitment in [CommitmentConfig::confirmed(), CommitmentConfig::finalized(), CommitmentConfig::finalized()] {
+        source.get_transaction_with_config(&signature, config(commitment)).await.expect("fetch succeeds");
+    }
+    assert_eq!(fixtures.fetches(), HashMap::from([(signature, 2)]));
+    assert_eq!(source.cache_hits(), 1);
+    fs::remove_dir_all(&dir).expect("cache dir removed");
+}
+
+#[tokio::test]
+async fn transfer_limit_keeps_the_rest_of_the_page_for_resume() {
+    let path = std::env::temp_dir().join(format!("transfers-checkpoint-{}.json", std::process::id()));
+    let source = FixtureSource::load(&["receive", "send"]);
+    let sent = source.fixtures[0].signature.to_string();
+    let received = source.fixtures[1].signature.to_string();
+    let signatures = |sink: &VecSink| sink.transfers.iter().map(|t| t.signature.clone()).collect::<Vec<_>>();
+
+    // One page holds both transactions, and the limit stops after the newer one
+    let filter = TransferFilter { max_transfers: Some(1), ..TransferFilter::default() };
+    let mut sink = VecSink::default();
+    let accepted = index_with_checkpoints(
+        &source,
+        WALLET,
+        &[USDC],
+        fixture_range(),
+        1000,
+        1,
+        1,
+        RetryPolicy::default(),
+        &filter,
+        CommitmentConfig::confirmed(),
+        &path,
+        1,
+        &mut sink,
+    )
+    .await
+    .expect("indexing succeeds");
+    assert_eq!(accepted, 1);
+    assert_eq!(signatures(&sink), vec![sent.clone()]);
+    let checkpoint = ScanCheckpoint::load(&path).expect("checkpoint saved");
+    assert_eq!((checkpoint.before, checkpoint.complete), (None, false));
+
+    let mut sink = VecSink::default();
+    let accepted = resume(
+        &source,
+        &path,
+        1000,
+        1,
+        1,
+        RetryPolicy::default(),
+        &TransferFilter::default(),
+        CommitmentConfig::confirmed(),
+        1,
+        &mut sink,
+    )
+    .await
+    .expect("resuming succeeds");
+    assert_eq!(accepted, 2);
+    assert_eq!(signatures(&sink), vec![received, sent]);
+    assert!(ScanCheckpoint::load(&path).expect("checkpoint saved").complete);
+    fs::remove_file(&path).expect("checkpoint removed");
+}
+
+#[tokio::test]
+async fn pool_fails_over_a_batch_the_endpoint_refused() {
+    // Nothing listens on port 1, so the batch request fails to connect
+    let unreachable = RpcClientConfig::default().connect("http://127.0.0.1:1").expect("valid config");
+    let fixtures = FixtureSource::load(&["receive"]);
+    let signature = fixtures.signature();
+    let pool = ClientPool::new(vec![&unreachable as &(dyn SolanaSource + Sync), &fixtures]);
+    let config = RpcTransactionConfig {
+        encoding: Some(UiTransactionEncoding::JsonParsed),
+        commitment: Some(CommitmentConfig::confirmed()),
+        max_supported_transaction_version: Some(0),
+    };
+
+    let results = pool.get_transactions_with_config(&[signature], config).await;
+    assert_eq!(results.len(), 1);
+    assert!(results[0].is_ok(), "batch not failed over: {:?}", results[0].as_ref().err());
+    assert_eq!(fixtures.fetches(), HashMap::from([(signature, 1)]));
+}
+
+#[tokio::test]
+async fn commitment_fallback_ages_transactions_by_the_filter_clock() {
+    let source = FixtureSource { unfinalized: true, ..FixtureSource::load(&["receive"]) };
+    let signature = source.signature();
+    let index_at = |seconds| {
+        let filter = TransferFilter {
+            commitment_fallback: Some(CommitmentFallback {
+                max_age: chrono::Duration::minutes(10),
+                commitment: CommitmentConfig::confirmed(),
+            }),
+            clock: Some(Arc::new(FixedClock(time(seconds)))),
+            ..TransferFilter::default()
+        };
+        let source = &source;
+        async move {
+            process_signatures(
+                source,
+                WALLET,
+                &[USDC],
+                &[signature],
+                fixture_range(),
+                1,
+                1,
+                RetryPolicy::default(),
+                &filter,
+                CommitmentConfig::finalized(),
+            )
+            .await
+            .expect("indexing succeeds")
+        }
+    };
+
+    // A minute after the transaction it is recent enough to fetch at `confirmed`
+    let recent = index_at(1_700_000_160).await;
+    assert!(recent.errors.is_empty(), "fetch errors: {:?}", recent.errors);
+    let commitments: Vec<_> = recent.transfers.iter().map(|t| t.commitment).collect();
+    assert_eq!(commitments, vec![Some(CommitmentLevel::Confirmed)]);
+
+    // A day later it should have been finalized, so not finding it is an error
+    let old = index_at(1_700_086_500).await;
+    assert_eq!(old.transfers, Vec::new());
+    assert_eq!(old.errors.len(), 1);
+}